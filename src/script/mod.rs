@@ -1,12 +1,19 @@
 use crate::{
     prelude::*,
     terminal::{
-        camera::{CameraResized, TerminalCamera2d},
+        action::{ActionEvent, ActionMap, InputContext, KeyChord},
+        camera::{CameraController, MainCamera, TerminalCamera2d},
         render::TextureRect,
+        screen::{Anchor, ScreenLength, ScreenSpace},
+        CellStyle,
     },
 };
 
-use bevy::input::keyboard::{ButtonState, KeyboardInput};
+use bevy::input::mouse::MouseWheel;
+use crossterm::style::{Attribute, Color};
+
+/// Overrides for the default key bindings, if the file exists.
+const BINDINGS_PATH: &str = "bindings.conf";
 
 #[derive(Default)]
 pub struct ScriptPlugin();
@@ -14,14 +21,40 @@ pub struct ScriptPlugin();
 impl Plugin for ScriptPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(handle_camera_movement_keys)
-            .add_system(handle_camera_resized)
+            .add_system(handle_camera_zoom_scroll)
+            .add_startup_system(bind_actions)
             .add_startup_system(spawn_textures);
     }
 }
 
+fn bind_actions(mut map: ResMut<ActionMap>) {
+    for (action, chord) in [
+        ("move_left", KeyChord::new(KeyCode::A)),
+        ("move_right", KeyChord::new(KeyCode::D)),
+        ("move_up", KeyChord::new(KeyCode::W)),
+        ("move_down", KeyChord::new(KeyCode::S)),
+        ("zoom_in", KeyChord::new(KeyCode::Equals)),
+        ("zoom_out", KeyChord::new(KeyCode::Minus)),
+        ("camera_back", KeyChord::new(KeyCode::Back)),
+        // '<' and '>'.
+        ("level_up", KeyChord::new(KeyCode::Comma).with_shift()),
+        ("level_down", KeyChord::new(KeyCode::Period).with_shift()),
+    ] {
+        if let Err(err) = map.bind(InputContext::Map, chord, action) {
+            log::error!("{}", err);
+        }
+    }
+    if std::path::Path::new(BINDINGS_PATH).exists() {
+        if let Err(err) = map.load_config(BINDINGS_PATH) {
+            log::error!("Couldn't load {}: {}", BINDINGS_PATH, err);
+        }
+    }
+}
+
 fn spawn_textures(mut cmd: Commands) {
     cmd.spawn(TextureRect {
         texture: 'a',
+        style: CellStyle::new(Color::Yellow, Color::Reset).with_attribute(Attribute::Bold),
         dim: Vec2::new(2.0, 1.0),
         loc: Vec2::new(0.0, 0.0),
         loc_z: 1.0,
@@ -41,124 +74,89 @@ fn spawn_textures(mut cmd: Commands) {
 
     let vert_wall = TextureRect {
         texture: '-',
+        style: CellStyle::default(),
         dim: Vec2::new(1.0, 2.0),
         loc: Vec2::new(0.0, 0.0),
         loc_z: 1000.0,
     };
     let side_wall = TextureRect {
         texture: '|',
+        style: CellStyle::default(),
         dim: Vec2::new(2.0, 1.0),
         loc: Vec2::new(0.0, 0.0),
         loc_z: 1000.0,
     };
+    // The frame lives in screen space so it follows the edges of the terminal
+    // on its own.
+    let full = ScreenLength::Percent(100.0);
+    let edge = ScreenLength::Cells(1);
     cmd.spawn_batch([
         CameraFrameWallBundle {
             texture: side_wall.clone(),
-            side: CameraSide::Right,
+            screen_space: ScreenSpace::new(Anchor::Right, edge, full),
         },
         CameraFrameWallBundle {
             texture: side_wall,
-            side: CameraSide::Left,
+            screen_space: ScreenSpace::new(Anchor::Left, edge, full),
         },
         CameraFrameWallBundle {
             texture: vert_wall.clone(),
-            side: CameraSide::Top,
+            screen_space: ScreenSpace::new(Anchor::Top, full, edge),
         },
         CameraFrameWallBundle {
             texture: vert_wall,
-            side: CameraSide::Bottom,
+            screen_space: ScreenSpace::new(Anchor::Bottom, full, edge),
         },
     ]);
 }
 
-/// Marker component type to indicate the CameraFrame Entity.
-#[derive(Component, Default)]
-struct CameraFrame {}
-
 #[derive(Bundle)]
 struct CameraFrameWallBundle {
     texture: TextureRect,
-    side: CameraSide,
-}
-
-#[derive(Component)]
-enum CameraSide {
-    Left,
-    Right,
-    Top,
-    Bottom,
+    screen_space: ScreenSpace,
 }
 
-fn handle_camera_resized(
-    mut walls: Query<(&mut TextureRect, &CameraSide)>,
-    camera: Res<TerminalCamera2d>,
-    mut event: EventReader<CameraResized>,
-) {
-    if let Some(event) = event.iter().last() {
-        center_camera_frame(&camera, &mut walls)
-    }
+fn move_camera(direction: Vec2, camera: &mut TerminalCamera2d) {
+    camera.move_by(Vec3::new(direction.x, direction.y, 0.0));
 }
 
-fn center_camera_frame(
-    camera: &TerminalCamera2d,
-    walls: &mut Query<(&mut TextureRect, &CameraSide)>,
+fn handle_camera_movement_keys(
+    mut actions: EventReader<ActionEvent>,
+    mut cameras: Query<(&mut TerminalCamera2d, &mut CameraController), With<MainCamera>>,
 ) {
-    for mut wall in walls.iter_mut() {
-        match *wall.1 {
-            CameraSide::Left => {
-                wall.0.loc.x = -camera.dim().x / 2.0 + 1.0 + camera.loc().x;
-                wall.0.loc.y = camera.loc().y;
-                wall.0.dim.x = 1.0;
-                wall.0.dim.y = camera.dim().y;
-            }
-            CameraSide::Right => {
-                wall.0.loc.x = camera.dim().x / 2.0 + camera.loc().x;
-                wall.0.loc.y = camera.loc().y;
-                wall.0.dim.x = 1.0;
-                wall.0.dim.y = camera.dim().y;
-            }
-            CameraSide::Top => {
-                wall.0.loc.x = camera.loc().x;
-                wall.0.loc.y = -camera.dim().y / 2.0 + 1.0 + camera.loc().y;
-                wall.0.dim.x = camera.dim().x;
-                wall.0.dim.y = 1.0;
-            }
-            CameraSide::Bottom => {
-                wall.0.loc.x = camera.loc().x;
-                wall.0.loc.y = camera.dim().y / 2.0 + camera.loc().y;
-                wall.0.dim.x = camera.dim().x;
-                wall.0.dim.y = 1.0;
+    let Ok((mut camera, mut controller)) = cameras.get_single_mut() else {
+        return;
+    };
+    for event in actions.iter() {
+        match event.action.as_str() {
+            "move_right" => move_camera(Vec2::new(1.0, 0.0), &mut camera),
+            "move_left" => move_camera(Vec2::new(-1.0, 0.0), &mut camera),
+            "move_up" => move_camera(Vec2::new(0.0, -1.0), &mut camera),
+            "move_down" => move_camera(Vec2::new(0.0, 1.0), &mut camera),
+            "zoom_in" => camera.zoom_in(),
+            "zoom_out" => camera.zoom_out(),
+            "level_up" => camera.level_up(),
+            "level_down" => camera.level_down(),
+            "camera_back" => {
+                controller.back();
             }
+            _ => (),
         }
     }
 }
 
-fn move_camera(
-    direction: Vec2,
-    camera: &mut ResMut<TerminalCamera2d>,
-    walls: &mut Query<(&mut TextureRect, &CameraSide)>,
+fn handle_camera_zoom_scroll(
+    mut wheel: EventReader<MouseWheel>,
+    mut cameras: Query<&mut TerminalCamera2d, With<MainCamera>>,
 ) {
-    camera.move_by(Vec3::new(direction.x, direction.y, 0.0));
-    center_camera_frame(&*camera, walls);
-}
-
-fn handle_camera_movement_keys(
-    mut input: EventReader<KeyboardInput>,
-    mut camera: ResMut<TerminalCamera2d>,
-    mut walls: Query<(&mut TextureRect, &CameraSide)>,
-) {
-    for e in input.iter() {
-        if e.state != ButtonState::Pressed {
-            continue;
-        }
-        if let Some(k) = e.key_code {
-            match k {
-                KeyCode::D => move_camera(Vec2::new(1.0, 0.0), &mut camera, &mut walls),
-                KeyCode::A => move_camera(Vec2::new(-1.0, 0.0), &mut camera, &mut walls),
-                KeyCode::W => move_camera(Vec2::new(0.0, -1.0), &mut camera, &mut walls),
-                KeyCode::S => move_camera(Vec2::new(0.0, 1.0), &mut camera, &mut walls),
-                _ => (),
-            }
+    let Ok(mut camera) = cameras.get_single_mut() else {
+        return;
+    };
+    for event in wheel.iter() {
+        if event.y > 0.0 {
+            camera.zoom_in();
+        } else if event.y < 0.0 {
+            camera.zoom_out();
         }
     }
 }
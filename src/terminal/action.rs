@@ -0,0 +1,552 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use bevy::input::keyboard::ButtonState;
+use bevy::utils::HashMap;
+
+use crate::prelude::*;
+
+use super::focus::{self, FocusedInput};
+use super::input::TerminalKeyInput;
+use super::widget::Widget;
+
+#[derive(Default)]
+pub struct TerminalActionPlugin();
+
+impl Plugin for TerminalActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActionMap>()
+            .add_event::<ActionEvent>()
+            .add_system(trigger_actions.after(focus::route_input));
+    }
+}
+
+/// Names of keys as written in bindings, the first name of a key is the one
+/// it's written back out as.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("a", KeyCode::A),
+    ("b", KeyCode::B),
+    ("c", KeyCode::C),
+    ("d", KeyCode::D),
+    ("e", KeyCode::E),
+    ("f", KeyCode::F),
+    ("g", KeyCode::G),
+    ("h", KeyCode::H),
+    ("i", KeyCode::I),
+    ("j", KeyCode::J),
+    ("k", KeyCode::K),
+    ("l", KeyCode::L),
+    ("m", KeyCode::M),
+    ("n", KeyCode::N),
+    ("o", KeyCode::O),
+    ("p", KeyCode::P),
+    ("q", KeyCode::Q),
+    ("r", KeyCode::R),
+    ("s", KeyCode::S),
+    ("t", KeyCode::T),
+    ("u", KeyCode::U),
+    ("v", KeyCode::V),
+    ("w", KeyCode::W),
+    ("x", KeyCode::X),
+    ("y", KeyCode::Y),
+    ("z", KeyCode::Z),
+    ("0", KeyCode::Key0),
+    ("1", KeyCode::Key1),
+    ("2", KeyCode::Key2),
+    ("3", KeyCode::Key3),
+    ("4", KeyCode::Key4),
+    ("5", KeyCode::Key5),
+    ("6", KeyCode::Key6),
+    ("7", KeyCode::Key7),
+    ("8", KeyCode::Key8),
+    ("9", KeyCode::Key9),
+    ("f1", KeyCode::F1),
+    ("f2", KeyCode::F2),
+    ("f3", KeyCode::F3),
+    ("f4", KeyCode::F4),
+    ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6),
+    ("f7", KeyCode::F7),
+    ("f8", KeyCode::F8),
+    ("f9", KeyCode::F9),
+    ("f10", KeyCode::F10),
+    ("f11", KeyCode::F11),
+    ("f12", KeyCode::F12),
+    ("f13", KeyCode::F13),
+    ("f14", KeyCode::F14),
+    ("f15", KeyCode::F15),
+    ("f16", KeyCode::F16),
+    ("f17", KeyCode::F17),
+    ("f18", KeyCode::F18),
+    ("f19", KeyCode::F19),
+    ("f20", KeyCode::F20),
+    ("f21", KeyCode::F21),
+    ("f22", KeyCode::F22),
+    ("f23", KeyCode::F23),
+    ("f24", KeyCode::F24),
+    ("esc", KeyCode::Escape),
+    ("escape", KeyCode::Escape),
+    ("enter", KeyCode::Return),
+    ("return", KeyCode::Return),
+    ("tab", KeyCode::Tab),
+    ("space", KeyCode::Space),
+    ("backspace", KeyCode::Back),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("comma", KeyCode::Comma),
+    ("period", KeyCode::Period),
+    ("slash", KeyCode::Slash),
+    ("backslash", KeyCode::Backslash),
+    ("semicolon", KeyCode::Semicolon),
+    ("colon", KeyCode::Colon),
+    ("apostrophe", KeyCode::Apostrophe),
+    ("grave", KeyCode::Grave),
+    ("minus", KeyCode::Minus),
+    ("equals", KeyCode::Equals),
+    ("plus", KeyCode::Plus),
+    ("asterisk", KeyCode::Asterisk),
+    ("at", KeyCode::At),
+    ("lbracket", KeyCode::LBracket),
+    ("rbracket", KeyCode::RBracket),
+    ("pause", KeyCode::Pause),
+];
+
+/// Modifier keys held down with a key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// The Windows, Command or Super key.
+    pub super_key: bool,
+}
+
+impl Modifiers {
+    /// Track a modifier key going up or down, returns whether `key` was a
+    /// modifier.
+    fn update(&mut self, key: KeyCode, state: ButtonState) -> bool {
+        let held = state == ButtonState::Pressed;
+        match key {
+            KeyCode::LShift | KeyCode::RShift => self.shift = held,
+            KeyCode::LControl | KeyCode::RControl => self.ctrl = held,
+            KeyCode::LAlt | KeyCode::RAlt => self.alt = held,
+            KeyCode::LWin | KeyCode::RWin => self.super_key = held,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// A key pressed along with some modifiers, e.g. `ctrl+s`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+impl KeyChord {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            modifiers: Modifiers::default(),
+        }
+    }
+
+    pub fn with_shift(mut self) -> Self {
+        self.modifiers.shift = true;
+        self
+    }
+
+    pub fn with_ctrl(mut self) -> Self {
+        self.modifiers.ctrl = true;
+        self
+    }
+
+    pub fn with_alt(mut self) -> Self {
+        self.modifiers.alt = true;
+        self
+    }
+
+    pub fn with_super(mut self) -> Self {
+        self.modifiers.super_key = true;
+        self
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.trim().to_lowercase();
+        let mut parts: Vec<&str> = lower.split('+').map(str::trim).collect();
+        let key_name = match parts.pop() {
+            // The key itself is '+' when it comes after a separator, as in
+            // `alt++`, otherwise it's missing.
+            Some("") => {
+                if parts.pop() != Some("") {
+                    return Err(format!("missing key in `{}`", s.trim()));
+                }
+                "plus"
+            }
+            Some(name) => name,
+            None => "",
+        };
+        let key = KEY_NAMES
+            .iter()
+            .find(|(name, _)| *name == key_name)
+            .map(|(_, key)| *key)
+            .ok_or_else(|| format!("unknown key `{}`", key_name))?;
+        let mut chord = KeyChord::new(key);
+        for modifier in parts.into_iter().filter(|part| !part.is_empty()) {
+            match modifier {
+                "shift" => chord.modifiers.shift = true,
+                "ctrl" | "control" => chord.modifiers.ctrl = true,
+                "alt" | "meta" => chord.modifiers.alt = true,
+                "super" | "win" | "cmd" => chord.modifiers.super_key = true,
+                other => return Err(format!("unknown modifier `{}`", other)),
+            }
+        }
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.ctrl {
+            write!(f, "ctrl+")?;
+        }
+        if self.modifiers.alt {
+            write!(f, "alt+")?;
+        }
+        if self.modifiers.shift {
+            write!(f, "shift+")?;
+        }
+        if self.modifiers.super_key {
+            write!(f, "super+")?;
+        }
+        match KEY_NAMES.iter().find(|(_, key)| *key == self.key) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => write!(f, "{:?}", self.key),
+        }
+    }
+}
+
+/// Which set of bindings applies, picked from whatever has focus.
+///
+/// Add this to an entity on the [`FocusStack`](super::focus::FocusStack) to
+/// choose its bindings, otherwise text inputs use `TextEntry`, other widgets
+/// `Menu` and when nothing has focus it's `Map`.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InputContext {
+    Map,
+    Menu,
+    TextEntry,
+}
+
+impl FromStr for InputContext {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "map" => Ok(InputContext::Map),
+            "menu" => Ok(InputContext::Menu),
+            "text" | "text_entry" => Ok(InputContext::TextEntry),
+            other => Err(format!("unknown context `{}`", other)),
+        }
+    }
+}
+
+/// Sent when a bound chord is pressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActionEvent {
+    pub action: String,
+    pub context: InputContext,
+    /// Whoever had focus, as in [`FocusedInput`].
+    pub target: Option<Entity>,
+}
+
+/// A chord was bound to two different actions in the same context.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BindingConflict {
+    pub context: InputContext,
+    pub chord: KeyChord,
+    pub existing: String,
+    pub action: String,
+}
+
+impl fmt::Display for BindingConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is bound to both `{}` and `{}` in {:?}",
+            self.chord, self.existing, self.action, self.context
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum ActionMapError {
+    Io(std::io::Error),
+    Syntax { line: usize, message: String },
+    Conflict(BindingConflict),
+}
+
+impl fmt::Display for ActionMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ActionMapError::Io(err) => write!(f, "{}", err),
+            ActionMapError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            ActionMapError::Conflict(conflict) => write!(f, "{}", conflict),
+        }
+    }
+}
+
+impl std::error::Error for ActionMapError {}
+
+/// Maps key chords to named actions, with a set of bindings per
+/// [`InputContext`].
+///
+/// Bindings can be loaded from a config file of the form:
+///
+/// ```text
+/// # Comments start with '#'.
+/// [map]
+/// quit = q, esc
+/// save = ctrl+s
+///
+/// [menu]
+/// close = esc
+/// ```
+///
+/// Each action listed replaces the bindings it had in that context.
+#[derive(Resource, Clone, Debug)]
+pub struct ActionMap {
+    bindings: HashMap<InputContext, HashMap<KeyChord, String>>,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        let mut map = Self::empty();
+        for key in [KeyCode::Escape, KeyCode::Q] {
+            map.bind(InputContext::Map, KeyChord::new(key), "quit")
+                .unwrap();
+        }
+        map
+    }
+}
+
+impl ActionMap {
+    /// A map without any bindings.
+    pub fn empty() -> Self {
+        Self {
+            bindings: HashMap::default(),
+        }
+    }
+
+    /// Bind `chord` to `action`, failing if the chord already does something
+    /// else in this context.
+    pub fn bind(
+        &mut self,
+        context: InputContext,
+        chord: KeyChord,
+        action: impl Into<String>,
+    ) -> Result<(), BindingConflict> {
+        let action = action.into();
+        let bindings = self.bindings.entry(context).or_default();
+        match bindings.get(&chord) {
+            Some(existing) if *existing != action => Err(BindingConflict {
+                context,
+                chord,
+                existing: existing.clone(),
+                action,
+            }),
+            _ => {
+                bindings.insert(chord, action);
+                Ok(())
+            }
+        }
+    }
+
+    /// Remove every binding of `action` in `context`.
+    pub fn unbind(&mut self, context: InputContext, action: &str) {
+        if let Some(bindings) = self.bindings.get_mut(&context) {
+            bindings.retain(|_, bound| bound != action);
+        }
+    }
+
+    /// The action `chord` triggers in `context`.
+    pub fn action(&self, context: InputContext, chord: KeyChord) -> Option<&str> {
+        self.bindings.get(&context)?.get(&chord).map(String::as_str)
+    }
+
+    /// Every chord bound to `action` in `context`.
+    pub fn chords(&self, context: InputContext, action: &str) -> Vec<KeyChord> {
+        let mut chords: Vec<KeyChord> = self
+            .bindings
+            .get(&context)
+            .into_iter()
+            .flatten()
+            .filter(|(_, bound)| *bound == action)
+            .map(|(chord, _)| *chord)
+            .collect();
+        chords.sort_by_key(|chord| chord.to_string());
+        chords
+    }
+
+    /// Apply the bindings from a config file, see [`ActionMap`] for the
+    /// format. Nothing is changed if the file has any errors.
+    pub fn load_config(&mut self, path: impl AsRef<Path>) -> Result<(), ActionMapError> {
+        let config = std::fs::read_to_string(path).map_err(ActionMapError::Io)?;
+        self.apply_config(&config)
+    }
+
+    /// Apply bindings from the text of a config file.
+    pub fn apply_config(&mut self, config: &str) -> Result<(), ActionMapError> {
+        let mut entries = Vec::new();
+        let mut context = None;
+        for (idx, line) in config.lines().enumerate() {
+            let syntax = |message: String| ActionMapError::Syntax {
+                line: idx + 1,
+                message,
+            };
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                context = Some(name.parse::<InputContext>().map_err(syntax)?);
+                continue;
+            }
+            let Some((action, chords)) = line.split_once('=') else {
+                return Err(syntax("expected `action = key, ...`".to_string()));
+            };
+            let context =
+                context.ok_or_else(|| syntax("binding outside of a [context]".to_string()))?;
+            let chords = chords
+                .split(',')
+                .filter(|chord| !chord.trim().is_empty())
+                .map(KeyChord::from_str)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(syntax)?;
+            entries.push((context, action.trim().to_string(), chords));
+        }
+
+        // Drop the old bindings of everything being rebound first, so keys
+        // can be swapped between actions.
+        let mut updated = self.clone();
+        for (context, action, _) in entries.iter() {
+            updated.unbind(*context, action);
+        }
+        for (context, action, chords) in entries {
+            for chord in chords {
+                updated
+                    .bind(context, chord, action.clone())
+                    .map_err(ActionMapError::Conflict)?;
+            }
+        }
+        *self = updated;
+        Ok(())
+    }
+}
+
+pub(super) fn trigger_actions(
+    mut input: EventReader<FocusedInput>,
+    map: Res<ActionMap>,
+    mut modifiers: Local<Modifiers>,
+    contexts: Query<&InputContext>,
+    widgets: Query<&Widget>,
+    mut writer: EventWriter<ActionEvent>,
+) {
+    for event in input.iter() {
+        let TerminalKeyInput::Key(input) = event.input else {
+            continue;
+        };
+        let Some(key) = input.key_code else {
+            continue;
+        };
+        if modifiers.update(key, input.state) || input.state != ButtonState::Pressed {
+            continue;
+        }
+        let context = match event.target {
+            None => InputContext::Map,
+            Some(target) => match (contexts.get(target), widgets.get(target)) {
+                (Ok(context), _) => *context,
+                (_, Ok(Widget::TextInput(_))) => InputContext::TextEntry,
+                _ => InputContext::Menu,
+            },
+        };
+        let chord = KeyChord {
+            key,
+            modifiers: *modifiers,
+        };
+        if let Some(action) = map.action(context, chord) {
+            writer.send(ActionEvent {
+                action: action.to_string(),
+                context,
+                target: event.target,
+            });
+        }
+    }
+}
+
+#[test]
+fn test_action_map() {
+    assert_eq!(
+        "Ctrl+Shift+S".parse::<KeyChord>(),
+        Ok(KeyChord::new(KeyCode::S).with_ctrl().with_shift())
+    );
+    assert_eq!(
+        "alt++".parse::<KeyChord>(),
+        Ok(KeyChord::new(KeyCode::Plus).with_alt())
+    );
+    assert_eq!(
+        KeyChord::new(KeyCode::F5).with_ctrl().to_string(),
+        "ctrl+f5"
+    );
+    assert_eq!("+".parse::<KeyChord>(), Ok(KeyChord::new(KeyCode::Plus)));
+    assert_eq!(
+        "super+q".parse::<KeyChord>(),
+        Ok(KeyChord::new(KeyCode::Q).with_super())
+    );
+    assert!("hyper+x".parse::<KeyChord>().is_err());
+    assert!("shift+".parse::<KeyChord>().is_err());
+
+    let mut map = ActionMap::empty();
+    map.apply_config(
+        "
+        [map]
+        move_left = a, left # Comments are ignored.
+        move_right = d
+        ",
+    )
+    .unwrap();
+    let a = KeyChord::new(KeyCode::A);
+    let d = KeyChord::new(KeyCode::D);
+    assert_eq!(map.action(InputContext::Map, a), Some("move_left"));
+    assert_eq!(map.action(InputContext::Menu, a), None);
+
+    // Swapping keys between actions isn't a conflict.
+    map.apply_config("[map]\nmove_left = d\nmove_right = a")
+        .unwrap();
+    assert_eq!(map.action(InputContext::Map, a), Some("move_right"));
+    assert_eq!(map.chords(InputContext::Map, "move_left"), vec![d]);
+
+    // Binding a key which already does something else is.
+    let err = map.apply_config("[map]\nquit = a").unwrap_err();
+    assert!(
+        matches!(err, ActionMapError::Conflict(ref conflict) if conflict.existing == "move_right")
+    );
+    assert_eq!(map.action(InputContext::Map, a), Some("move_right"));
+
+    let err = map.apply_config("[map]\nquit = a\n[nowhere]").unwrap_err();
+    assert!(matches!(err, ActionMapError::Syntax { line: 3, .. }));
+}
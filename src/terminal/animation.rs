@@ -0,0 +1,270 @@
+use std::time::Duration;
+
+use crate::prelude::*;
+
+use super::display::CellStyle;
+use super::render::TextureRect;
+use super::spatial;
+
+#[derive(Default)]
+pub struct TerminalAnimationPlugin();
+
+impl Plugin for TerminalAnimationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Time>()
+            .add_event::<AnimationFinished>()
+            .add_system(animate.before(spatial::update_spatial_index));
+    }
+}
+
+/// Sent when an [`AnimationMode::Once`] animation shows its last frame for
+/// its full duration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationFinished(pub Entity);
+
+/// A single frame of an [`Animation`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AnimationFrame {
+    pub glyph: char,
+    pub style: CellStyle,
+    pub duration: Duration,
+}
+
+impl AnimationFrame {
+    pub fn new(glyph: char, style: CellStyle, duration: Duration) -> Self {
+        Self {
+            glyph,
+            style,
+            duration,
+        }
+    }
+}
+
+/// What an animation does once it reaches its last frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AnimationMode {
+    /// Start again from the first frame.
+    #[default]
+    Loop,
+    /// Stay on the last frame and send [`AnimationFinished`].
+    Once,
+    /// Play backwards to the first frame, then forwards again.
+    PingPong,
+}
+
+/// Cycles the glyph and style of the entity's [`TextureRect`] through a list
+/// of frames.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Animation {
+    frames: Vec<AnimationFrame>,
+    pub mode: AnimationMode,
+    frame: usize,
+    /// Time spent on the current frame.
+    elapsed: Duration,
+    reverse: bool,
+    finished: bool,
+}
+
+impl Animation {
+    pub fn new(frames: Vec<AnimationFrame>) -> Self {
+        Self {
+            frames,
+            mode: AnimationMode::default(),
+            frame: 0,
+            elapsed: Duration::ZERO,
+            reverse: false,
+            finished: false,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: AnimationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    /// Index of the frame being shown.
+    pub fn frame_index(&self) -> usize {
+        self.frame
+    }
+
+    pub fn frame(&self) -> Option<&AnimationFrame> {
+        self.frames.get(self.frame)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Go back to the first frame, playing a finished animation again.
+    pub fn restart(&mut self) {
+        self.frame = 0;
+        self.elapsed = Duration::ZERO;
+        self.reverse = false;
+        self.finished = false;
+    }
+
+    /// Move time forward by `delta`, returns whether the animation is now on
+    /// a different frame or just finished.
+    fn advance(&mut self, delta: Duration) -> bool {
+        if self.finished || self.frames.iter().all(|frame| frame.duration.is_zero()) {
+            return false;
+        }
+        let start = self.frame;
+        self.elapsed += delta;
+        while self.elapsed >= self.frames[self.frame].duration {
+            self.elapsed -= self.frames[self.frame].duration;
+            if !self.step() {
+                self.finished = true;
+                self.elapsed = Duration::ZERO;
+                return true;
+            }
+        }
+        self.frame != start
+    }
+
+    /// Move to the next frame, `false` if there isn't one.
+    fn step(&mut self) -> bool {
+        let last = self.frames.len() - 1;
+        match self.mode {
+            AnimationMode::Loop => {
+                self.frame = if self.frame == last {
+                    0
+                } else {
+                    self.frame + 1
+                }
+            }
+            AnimationMode::Once if self.frame == last => return false,
+            AnimationMode::Once => self.frame += 1,
+            AnimationMode::PingPong if last == 0 => (),
+            AnimationMode::PingPong => {
+                if self.frame == last {
+                    self.reverse = true;
+                } else if self.frame == 0 {
+                    self.reverse = false;
+                }
+                if self.reverse {
+                    self.frame -= 1;
+                } else {
+                    self.frame += 1;
+                }
+            }
+        }
+        true
+    }
+}
+
+fn animate(
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut Animation, &mut TextureRect)>,
+    mut finished_writer: EventWriter<AnimationFinished>,
+) {
+    let delta = time.delta();
+    for (entity, mut animation, mut texture) in query.iter_mut() {
+        // Don't touch the texture unless the frame moved on, so the renderer
+        // only redraws what actually changed.
+        let changed = animation.advance(delta) || animation.is_added();
+        if !changed {
+            continue;
+        }
+        if animation.is_finished() {
+            finished_writer.send(AnimationFinished(entity));
+        }
+        if let Some(frame) = animation.frame() {
+            if texture.texture != frame.glyph || texture.style != frame.style {
+                texture.texture = frame.glyph;
+                texture.style = frame.style;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_animation_modes() {
+    let frames: Vec<AnimationFrame> = ['a', 'b', 'c']
+        .into_iter()
+        .map(|glyph| AnimationFrame::new(glyph, CellStyle::default(), Duration::from_millis(100)))
+        .collect();
+    let play = |mode, steps: usize| {
+        let mut animation = Animation::new(frames.clone()).with_mode(mode);
+        let glyphs: String = (0..steps)
+            .map(|_| {
+                animation.advance(Duration::from_millis(100));
+                animation.frame().unwrap().glyph
+            })
+            .collect();
+        (glyphs, animation.is_finished())
+    };
+    assert_eq!(play(AnimationMode::Loop, 5), ("bcabc".to_string(), false));
+    assert_eq!(play(AnimationMode::Once, 3), ("bcc".to_string(), true));
+    assert_eq!(
+        play(AnimationMode::PingPong, 6),
+        ("bcbabc".to_string(), false)
+    );
+
+    // Long frames hold until their whole duration has passed, short ones can
+    // be skipped over within a single update.
+    let mut animation = Animation::new(vec![
+        AnimationFrame::new('x', CellStyle::default(), Duration::from_millis(300)),
+        AnimationFrame::new('y', CellStyle::default(), Duration::from_millis(10)),
+    ]);
+    assert!(!animation.advance(Duration::from_millis(200)));
+    assert!(!animation.advance(Duration::from_millis(110)));
+    assert_eq!(animation.frame().unwrap().glyph, 'x');
+}
+
+#[test]
+fn test_animate_system() {
+    use crossterm::style::Color;
+
+    let mut app = App::new();
+    app.add_plugin(TerminalAnimationPlugin::default());
+    let red = CellStyle::new(Color::Red, Color::Reset);
+    let blue = CellStyle::new(Color::Blue, Color::Reset);
+    let entity = app
+        .world
+        .spawn((
+            TextureRect {
+                texture: '?',
+                style: CellStyle::default(),
+                dim: Vec2::ONE,
+                loc: Vec2::ZERO,
+                loc_z: 0.0,
+            },
+            Animation::new(vec![
+                AnimationFrame::new('a', red, Duration::from_millis(100)),
+                AnimationFrame::new('b', blue, Duration::from_millis(100)),
+            ])
+            .with_mode(AnimationMode::Once),
+        ))
+        .id();
+    let mut finished = app
+        .world
+        .resource::<Events<AnimationFinished>>()
+        .get_reader();
+    let mut millis = 0;
+    let mut step = |app: &mut App, elapsed: u64| {
+        millis += elapsed;
+        let mut time = app.world.resource_mut::<Time>();
+        let now = time.startup() + Duration::from_millis(millis);
+        time.update_with_instant(now);
+        app.update();
+        let texture = app.world.get::<TextureRect>(entity).unwrap();
+        let events = app.world.resource::<Events<AnimationFinished>>();
+        let sent: Vec<_> = finished.iter(events).copied().collect();
+        (texture.texture, texture.style, sent)
+    };
+    // The first frame shows as soon as the animation is added.
+    assert_eq!(step(&mut app, 0), ('a', red, vec![]));
+    assert_eq!(step(&mut app, 50), ('a', red, vec![]));
+    assert_eq!(step(&mut app, 50), ('b', blue, vec![]));
+    assert_eq!(
+        step(&mut app, 100),
+        ('b', blue, vec![AnimationFinished(entity)])
+    );
+    assert_eq!(step(&mut app, 100), ('b', blue, vec![]));
+    assert_eq!(step(&mut app, 1000), ('b', blue, vec![]));
+}
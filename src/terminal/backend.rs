@@ -0,0 +1,484 @@
+use std::collections::VecDeque;
+use std::io::{stdout, Stdout, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crossterm::event::{
+    poll, read, DisableBracketedPaste, DisableFocusChange, DisableMouseCapture,
+    EnableBracketedPaste, EnableFocusChange, EnableMouseCapture, Event, KeyboardEnhancementFlags,
+    PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+};
+use crossterm::execute;
+use crossterm::style::{Attribute, Colored};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, size, supports_keyboard_enhancement, EnterAlternateScreen,
+    LeaveAlternateScreen,
+};
+
+use crate::prelude::*;
+use crate::util::on_exit::Callback;
+
+use super::display::{glyph_width, Cell, CellStyle};
+
+/// The terminal the plugins draw to and read input from.
+///
+/// Everything which touches the real terminal goes through this so the
+/// plugins can be run without a TTY, see [`HeadlessBackend`].
+pub trait TerminalBackend: Send + Sync + 'static {
+    /// Prepare the terminal for drawing.
+    fn init(&mut self) -> std::io::Result<()>;
+
+    /// Callback which restores the terminal once the app exits, if there's
+    /// anything to restore.
+    fn on_exit(&self) -> Option<Callback>;
+
+    /// Current size of the terminal in (columns, rows).
+    fn size(&self) -> std::io::Result<(u16, u16)>;
+
+    /// Where painted frames are written.
+    fn writer(&mut self) -> &mut dyn Write;
+
+    /// Start collecting input events.
+    fn start_input(&mut self);
+
+    /// Take all input events received since the last call, oldest first.
+    ///
+    /// An error means reading input failed and no more events will arrive.
+    fn poll_events(&mut self) -> Vec<std::io::Result<Event>>;
+
+    /// Stop collecting input events, waiting for anything reading them to
+    /// finish.
+    fn stop_input(&mut self);
+}
+
+/// Resource holding the active backend.
+///
+/// Insert this before adding the `TerminalPlugin` to pick a backend,
+/// otherwise the plugins fall back to the [`CrosstermBackend`].
+#[derive(Resource)]
+pub struct Terminal(Box<dyn TerminalBackend>);
+
+impl Terminal {
+    pub fn new(backend: impl TerminalBackend) -> Self {
+        Self(Box::new(backend))
+    }
+
+    pub fn backend(&self) -> &dyn TerminalBackend {
+        &*self.0
+    }
+
+    pub fn backend_mut(&mut self) -> &mut dyn TerminalBackend {
+        &mut *self.0
+    }
+}
+
+/// Make sure there's a backend to build the terminal plugins on top of.
+pub(super) fn init_default_backend(app: &mut App) {
+    if !app.world.contains_resource::<Terminal>() {
+        app.insert_resource(Terminal::new(CrosstermBackend::default()));
+    }
+}
+
+/// Backend driving the real terminal through crossterm.
+pub struct CrosstermBackend {
+    stdout: Stdout,
+    input: Option<InputThread>,
+}
+
+impl Default for CrosstermBackend {
+    fn default() -> Self {
+        Self {
+            stdout: stdout(),
+            input: None,
+        }
+    }
+}
+
+impl Drop for CrosstermBackend {
+    fn drop(&mut self) {
+        self.stop_input();
+    }
+}
+
+impl TerminalBackend for CrosstermBackend {
+    fn init(&mut self) -> std::io::Result<()> {
+        enable_raw_mode()?;
+        execute!(
+            self.stdout,
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableBracketedPaste,
+            EnableFocusChange,
+            crossterm::cursor::Hide
+        )?;
+        // Ask for key releases and repeats where the terminal can report
+        // them, otherwise the input plugin has to guess when keys are let go.
+        if supports_keyboard_enhancement().unwrap_or(false) {
+            execute!(
+                self.stdout,
+                PushKeyboardEnhancementFlags(
+                    KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+                        | KeyboardEnhancementFlags::REPORT_EVENT_TYPES
+                )
+            )?;
+            KEYBOARD_ENHANCED.store(true, Ordering::SeqCst);
+        }
+        Ok(())
+    }
+
+    fn on_exit(&self) -> Option<Callback> {
+        Some(crossterm_cleanup)
+    }
+
+    fn size(&self) -> std::io::Result<(u16, u16)> {
+        size()
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        &mut self.stdout
+    }
+
+    fn start_input(&mut self) {
+        if self.input.is_none() {
+            self.input = Some(InputThread::spawn());
+        }
+    }
+
+    fn poll_events(&mut self) -> Vec<std::io::Result<Event>> {
+        match &self.input {
+            Some(input) => input.events.lock().unwrap().try_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn stop_input(&mut self) {
+        if let Some(input) = self.input.take() {
+            input.stop();
+        }
+        // This has to happen before the cleanup leaves the alternate screen,
+        // terminals keep a separate stack of flags for each screen.
+        pop_keyboard_enhancement(&mut self.stdout);
+    }
+}
+
+/// Whether keyboard enhancement flags were pushed and need popping.
+///
+/// Exits which skip stopping input (a panic, or `atexit` alone) still run
+/// [`crossterm_cleanup`], and it can only read state like this.
+static KEYBOARD_ENHANCED: AtomicBool = AtomicBool::new(false);
+
+fn pop_keyboard_enhancement(stdout: &mut Stdout) {
+    if KEYBOARD_ENHANCED.swap(false, Ordering::SeqCst) {
+        if let Err(err) = execute!(stdout, PopKeyboardEnhancementFlags) {
+            log::error!("Failed to restore keyboard reporting: {}", err);
+        }
+    }
+}
+
+fn crossterm_cleanup() {
+    log::info!("Performing terminal cleanup");
+    pop_keyboard_enhancement(&mut stdout());
+    disable_raw_mode().unwrap();
+    execute!(
+        stdout(),
+        DisableMouseCapture,
+        DisableBracketedPaste,
+        DisableFocusChange,
+        LeaveAlternateScreen,
+        crossterm::cursor::Show
+    )
+    .unwrap();
+}
+
+/// Thread reading the terminal's events and sending them on as they arrive.
+struct InputThread {
+    // Only ever used through `&mut self`, the lock is just to make it `Sync`.
+    events: Mutex<Receiver<std::io::Result<Event>>>,
+    stop: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl InputThread {
+    /// How long to wait for input before checking whether to stop.
+    const STOP_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_millis(50);
+
+    fn spawn() -> Self {
+        let (sender, events) = channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = Arc::clone(&stop);
+        let handle = std::thread::spawn(move || Self::run(sender, thread_stop));
+        Self {
+            events: Mutex::new(events),
+            stop,
+            handle,
+        }
+    }
+
+    fn run(sender: Sender<std::io::Result<Event>>, stop: Arc<AtomicBool>) {
+        while !stop.load(Ordering::Relaxed) {
+            // `poll` returns as soon as there's an event, the timeout only
+            // bounds how long stopping takes.
+            let event = match poll(Self::STOP_CHECK_INTERVAL) {
+                Ok(false) => continue,
+                Ok(true) => read(),
+                Err(err) => Err(err),
+            };
+            // Errors are passed on too, then the thread stops as reading is
+            // unlikely to recover. It also stops once nobody is listening.
+            let failed = event.is_err();
+            if sender.send(event).is_err() || failed {
+                return;
+            }
+        }
+    }
+
+    fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if self.handle.join().is_err() {
+            log::error!("Input thread panicked");
+        }
+    }
+}
+
+/// In-memory backend for tests and running without a TTY.
+///
+/// Painted output is interpreted into a grid of cells and input is whatever
+/// gets scripted through the [`HeadlessHandle`].
+pub struct HeadlessBackend {
+    state: Arc<Mutex<HeadlessState>>,
+    pending: Vec<u8>,
+}
+
+impl HeadlessBackend {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(HeadlessState::new(width, height))),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Handle for inspecting the painted grid and scripting input once the
+    /// backend has been moved into the app.
+    pub fn handle(&self) -> HeadlessHandle {
+        HeadlessHandle(Arc::clone(&self.state))
+    }
+}
+
+impl TerminalBackend for HeadlessBackend {
+    fn init(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn on_exit(&self) -> Option<Callback> {
+        None
+    }
+
+    fn size(&self) -> std::io::Result<(u16, u16)> {
+        let state = self.state.lock().unwrap();
+        Ok((state.width, state.height))
+    }
+
+    fn writer(&mut self) -> &mut dyn Write {
+        self
+    }
+
+    fn start_input(&mut self) {}
+
+    fn poll_events(&mut self) -> Vec<std::io::Result<Event>> {
+        self.state.lock().unwrap().events.drain(..).collect()
+    }
+
+    fn stop_input(&mut self) {}
+}
+
+impl Write for HeadlessBackend {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let consumed = self.state.lock().unwrap().interpret(&self.pending);
+        self.pending.drain(..consumed);
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+pub struct HeadlessHandle(Arc<Mutex<HeadlessState>>);
+
+impl HeadlessHandle {
+    /// Queue an input event to be picked up on the next frame.
+    pub fn push_event(&self, event: Event) {
+        self.0.lock().unwrap().events.push_back(Ok(event));
+    }
+
+    /// Make reading input fail, as it would if the terminal went away.
+    pub fn fail_input(&self, err: std::io::Error) {
+        self.0.lock().unwrap().events.push_back(Err(err));
+    }
+
+    /// Change the size of the fake terminal, sending a resize event as a real
+    /// one would.
+    pub fn resize(&self, width: u16, height: u16) {
+        let mut state = self.0.lock().unwrap();
+        state.width = width;
+        state.height = height;
+        state.cells = vec![Cell::default(); width as usize * height as usize];
+        state.events.push_back(Ok(Event::Resize(width, height)));
+    }
+
+    pub fn cell(&self, col: u16, row: u16) -> Option<Cell> {
+        let state = self.0.lock().unwrap();
+        if col >= state.width || row >= state.height {
+            return None;
+        }
+        Some(state.cells[col as usize + row as usize * state.width as usize])
+    }
+
+    /// The painted grid as one string per row, the right half of wide glyphs
+    /// is left out so each string reads as it would on screen.
+    pub fn rows(&self) -> Vec<String> {
+        let state = self.0.lock().unwrap();
+        state
+            .cells
+            .chunks(state.width.max(1) as usize)
+            .map(|row| {
+                row.iter()
+                    .filter(|cell| !cell.is_continuation())
+                    .map(|cell| cell.glyph)
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+struct HeadlessState {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    cursor: (u16, u16),
+    style: CellStyle,
+    events: VecDeque<std::io::Result<Event>>,
+}
+
+impl HeadlessState {
+    fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+            cursor: (0, 0),
+            style: CellStyle::default(),
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Apply written output to the grid. Only understands the handful of
+    /// sequences the painter emits. Returns how many bytes were consumed,
+    /// anything left over is an incomplete sequence.
+    fn interpret(&mut self, bytes: &[u8]) -> usize {
+        let mut idx = 0;
+        while idx < bytes.len() {
+            let rest = &bytes[idx..];
+            if rest[0] == 0x1b {
+                match parse_escape(rest) {
+                    Some((len, sequence)) => {
+                        if let Some((params, action)) = sequence {
+                            self.apply_csi(params, action);
+                        }
+                        idx += len;
+                    }
+                    None => return idx,
+                }
+                continue;
+            }
+            let len = utf8_len(rest[0]);
+            if rest.len() < len {
+                return idx;
+            }
+            if let Some(glyph) = std::str::from_utf8(&rest[..len])
+                .ok()
+                .and_then(|s| s.chars().next())
+            {
+                if !glyph.is_control() {
+                    self.put(glyph);
+                }
+            }
+            idx += len;
+        }
+        idx
+    }
+
+    fn apply_csi(&mut self, params: &str, action: char) {
+        match action {
+            'H' => {
+                let mut coords = params.split(';').map(|p| p.parse::<u16>().unwrap_or(1));
+                let row = coords.next().unwrap_or(1).saturating_sub(1);
+                let col = coords.next().unwrap_or(1).saturating_sub(1);
+                self.cursor = (col, row);
+            }
+            'J' if params == "2" => self.cells.fill(Cell::default()),
+            'm' => self.apply_sgr(params),
+            _ => (),
+        }
+    }
+
+    fn apply_sgr(&mut self, params: &str) {
+        if params.is_empty() || params == "0" {
+            self.style = CellStyle::default();
+        } else if let Some(colored) = Colored::parse_ansi(params) {
+            match colored {
+                Colored::ForegroundColor(fg) => self.style.fg = fg,
+                Colored::BackgroundColor(bg) => self.style.bg = bg,
+                _ => (),
+            }
+        } else if let Some(attr) = Attribute::iterator().find(|attr| attr.sgr() == params) {
+            self.style.attrs.set(attr);
+        }
+    }
+
+    fn put(&mut self, glyph: char) {
+        let (col, row) = self.cursor;
+        if row >= self.height {
+            return;
+        }
+        let width = glyph_width(glyph);
+        let idx = col as usize + row as usize * self.width as usize;
+        if col < self.width {
+            self.cells[idx] = Cell::new(glyph, self.style);
+        }
+        if width > 1 && col + 1 < self.width {
+            self.cells[idx + 1] = Cell::continuation(self.style);
+        }
+        // Emulate autowrap, the full repaint depends on it.
+        self.cursor.0 += width;
+        if self.cursor.0 >= self.width {
+            self.cursor = (0, row + 1);
+        }
+    }
+}
+
+/// Parse an escape sequence at the start of `bytes`, returning its length and,
+/// for CSI sequences, the parameters and final character.
+fn parse_escape(bytes: &[u8]) -> Option<(usize, Option<(&str, char)>)> {
+    match bytes.get(1)? {
+        b'[' => {
+            let end = bytes[2..].iter().position(|b| (0x40..=0x7e).contains(b))? + 2;
+            let params = std::str::from_utf8(&bytes[2..end]).unwrap_or("");
+            Some((end + 1, Some((params, bytes[end] as char))))
+        }
+        _ => Some((2, None)),
+    }
+}
+
+fn utf8_len(first: u8) -> usize {
+    match first {
+        0xf0.. => 4,
+        0xe0.. => 3,
+        0xc0.. => 2,
+        _ => 1,
+    }
+}
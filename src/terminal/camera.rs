@@ -1,55 +1,372 @@
+use bevy::ecs::system::SystemParam;
+
 use crate::prelude::*;
 
-use super::input::TerminalResize;
+use super::backend;
+use super::display::{self, TerminalDisplayBuffer};
+use super::render::{self, CellRect, TextureRect};
+use super::screen::ScreenSpace;
 
 #[derive(Default)]
 pub struct TerminalCamera2dPlugin();
 
-#[derive(Default)]
-pub struct CameraResized(pub Vec2);
+/// A camera's `dim` was changed to fit its viewport.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraResized {
+    pub camera: Entity,
+    pub dim: Vec2,
+}
 
 impl Plugin for TerminalCamera2dPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(TerminalCamera2d::default())
-            .add_startup_system(init_camera_autosize)
+        backend::init_default_backend(app);
+        app.world.spawn((
+            TerminalCamera2d::default(),
+            CameraController::default(),
+            MainCamera,
+        ));
+        app.init_resource::<Time>()
             .add_event::<CameraResized>()
-            .add_system(handle_terminal_resize);
+            .add_system(fit_cameras_to_viewports.after(display::handle_terminal_resize))
+            .add_system(
+                update_camera_controller
+                    .after(fit_cameras_to_viewports)
+                    .before(render::render),
+            );
     }
 }
 
-fn init_camera_autosize(
-    mut camera: ResMut<TerminalCamera2d>,
+/// Marks the camera showing the map, the one the plugin spawns. Other
+/// cameras (minimaps and the like) are spawned as needed.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct MainCamera;
+
+/// Keep autoresizing cameras the size of their viewports.
+fn fit_cameras_to_viewports(
+    display_buf: Res<TerminalDisplayBuffer>,
+    mut cameras: Query<(Entity, &mut TerminalCamera2d)>,
     mut camera_event_writer: EventWriter<CameraResized>,
 ) {
-    if camera.settings_ref().autoresize() {
-        let term_size = crossterm::terminal::size().unwrap();
-        let update = Vec2::new(term_size.0 as f32, term_size.1 as f32);
-        camera.set_dim(update);
-        camera_event_writer.send(CameraResized(update));
+    let screen = UVec2::new(display_buf.0.width as u32, display_buf.0.height as u32);
+    for (entity, mut camera) in cameras.iter_mut() {
+        if !camera.settings_ref().autoresize() {
+            continue;
+        }
+        let dim = camera.viewport_rect(screen).size().as_vec2();
+        if dim != camera.dim() {
+            camera.set_dim(dim);
+            camera_event_writer.send(CameraResized {
+                camera: entity,
+                dim,
+            });
+        }
     }
 }
 
-fn handle_terminal_resize(
-    mut camera: ResMut<TerminalCamera2d>,
-    mut resize_reader: EventReader<TerminalResize>,
-    mut camera_event_writer: EventWriter<CameraResized>,
+/// Moves the [`TerminalCamera2d`] it's on for you: following an entity, keeping the
+/// view inside the world and panning smoothly rather than snapping.
+#[derive(Component, Clone, Debug, Default)]
+pub struct CameraController {
+    /// Entity kept in view, by its [`TextureRect`].
+    follow: Option<Entity>,
+    /// How far the followed entity can get from the center of the view, in
+    /// world units, before the camera moves to keep up.
+    pub dead_zone: Vec2,
+    /// The view is kept inside this area of the world, or centered on it if
+    /// the view is bigger.
+    pub bounds: Option<Rect>,
+    /// World units per second the camera pans at, `None` to move instantly.
+    pub pan_speed: Option<f32>,
+    /// Where the camera is panning to.
+    goal: Option<Vec2>,
+    /// An entity to jump to on the next update.
+    jump: Option<Entity>,
+    /// Where the camera was before each jump, most recent last.
+    history: Vec<Vec2>,
+}
+
+impl CameraController {
+    pub fn with_dead_zone(mut self, dead_zone: Vec2) -> Self {
+        self.dead_zone = dead_zone;
+        self
+    }
+
+    pub fn with_bounds(mut self, bounds: Rect) -> Self {
+        self.bounds = Some(bounds);
+        self
+    }
+
+    pub fn with_pan_speed(mut self, speed: f32) -> Self {
+        self.pan_speed = Some(speed);
+        self
+    }
+
+    /// Keep `entity` in view as it moves.
+    pub fn follow(&mut self, entity: Entity) {
+        self.follow = Some(entity);
+    }
+
+    pub fn stop_following(&mut self) {
+        self.follow = None;
+    }
+
+    pub fn following(&self) -> Option<Entity> {
+        self.follow
+    }
+
+    /// Pan over to center the view on `loc`.
+    pub fn pan_to(&mut self, loc: Vec2) {
+        self.goal = Some(loc);
+    }
+
+    /// Where the camera is panning to, if it's still on its way.
+    pub fn goal(&self) -> Option<Vec2> {
+        self.goal
+    }
+
+    /// Center the view on `entity`, remembering where we were so
+    /// [`back`](Self::back) can return there. Stops following anything else.
+    pub fn jump_to(&mut self, entity: Entity) {
+        self.jump = Some(entity);
+        self.follow = None;
+    }
+
+    /// Return to where the camera was before the last jump, `false` if
+    /// there's nowhere to go back to.
+    pub fn back(&mut self) -> bool {
+        match self.history.pop() {
+            Some(loc) => {
+                self.follow = None;
+                self.jump = None;
+                self.goal = Some(loc);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Places [`back`](Self::back) can return to, most recent last.
+    pub fn history(&self) -> &[Vec2] {
+        &self.history
+    }
+
+    /// Move `center` as little as possible to keep the view in bounds.
+    fn clamp(&self, center: Vec2, view: Vec2) -> Vec2 {
+        let Some(bounds) = self.bounds else {
+            return center;
+        };
+        let half = view / 2.0;
+        let (min, max) = (bounds.min + half, bounds.max - half);
+        // Too small to fit the view, just center it.
+        let fit = |center: f32, min: f32, max: f32, mid: f32| {
+            if min > max {
+                mid
+            } else {
+                center.clamp(min, max)
+            }
+        };
+        let mid = bounds.center();
+        Vec2::new(
+            fit(center.x, min.x, max.x, mid.x),
+            fit(center.y, min.y, max.y, mid.y),
+        )
+    }
+}
+
+fn update_camera_controller(
+    time: Res<Time>,
+    mut cameras: Query<(&mut CameraController, &mut TerminalCamera2d)>,
+    targets: Query<&TextureRect>,
 ) {
-    if !camera.settings_ref().autoresize() {
-        return;
+    for (mut controller, mut camera) in cameras.iter_mut() {
+        // The controller's bookkeeping changes most frames, that doesn't mark
+        // it changed. The camera is only written to when it actually moves,
+        // a changed camera means a full redraw.
+        control_camera(
+            controller.bypass_change_detection(),
+            &mut camera,
+            time.delta_seconds(),
+            &targets,
+        );
+    }
+}
+
+fn control_camera(
+    controller: &mut CameraController,
+    camera: &mut Mut<TerminalCamera2d>,
+    delta_seconds: f32,
+    targets: &Query<&TextureRect>,
+) {
+    let current = camera.loc().truncate();
+    let mut goal = controller.goal.unwrap_or(current);
+
+    if let Some(entity) = controller.jump.take() {
+        if let Ok(target) = targets.get(entity) {
+            controller.history.push(goal);
+            goal = target.loc;
+        }
+    }
+    if let Some(entity) = controller.follow {
+        match targets.get(entity) {
+            Ok(target) => {
+                let offset = target.loc - goal;
+                let outside = (offset.abs() - controller.dead_zone).max(Vec2::ZERO);
+                goal += outside * offset.signum();
+            }
+            // It's gone, nothing to follow.
+            Err(_) => controller.follow = None,
+        }
+    }
+    let goal = controller.clamp(goal, camera.world_rect().size());
+
+    let next = match controller.pan_speed {
+        Some(speed) => {
+            let step = speed * delta_seconds;
+            let remaining = goal - current;
+            if remaining.length() <= step {
+                goal
+            } else {
+                current + remaining.normalize() * step
+            }
+        }
+        None => goal,
+    };
+    controller.goal = (next != goal).then_some(goal);
+    if next != current {
+        let z = camera.z();
+        camera.set_loc(next.extend(z));
     }
-    if let Some(resize) = resize_reader.iter().last() {
-        let update = Vec2::new(resize.width as f32, resize.height as f32);
-        if update != camera.dim() {
-            camera.set_dim(update);
-            camera_event_writer.send(CameraResized(update));
+}
+
+/// Most a camera can zoom in or out, in tiles per cell or cells per tile.
+pub const MAX_ZOOM: u8 = 8;
+
+/// How much of the world fits in each terminal cell.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Zoom {
+    /// One world tile per cell.
+    #[default]
+    Normal,
+    /// Each world tile spans NxN cells.
+    In(u8),
+    /// Each cell summarizes an NxN block of world tiles, see
+    /// [`GlyphAggregation`].
+    Out(u8),
+}
+
+impl Zoom {
+    /// World units covered by one cell along each axis.
+    pub fn world_per_cell(&self) -> f32 {
+        match *self {
+            Zoom::Normal => 1.0,
+            Zoom::In(n) => 1.0 / n.max(1) as f32,
+            Zoom::Out(n) => n.max(1) as f32,
+        }
+    }
+
+    /// The next level in, stopping at [`MAX_ZOOM`].
+    pub fn zoomed_in(&self) -> Zoom {
+        match *self {
+            Zoom::Out(n) if n > 2 => Zoom::Out(n - 1),
+            Zoom::Out(_) => Zoom::Normal,
+            Zoom::Normal => Zoom::In(2),
+            Zoom::In(n) => Zoom::In(n.saturating_add(1).clamp(2, MAX_ZOOM)),
+        }
+    }
+
+    /// The next level out, stopping at [`MAX_ZOOM`].
+    pub fn zoomed_out(&self) -> Zoom {
+        match *self {
+            Zoom::In(n) if n > 2 => Zoom::In(n - 1),
+            Zoom::In(_) => Zoom::Normal,
+            Zoom::Normal => Zoom::Out(2),
+            Zoom::Out(n) => Zoom::Out(n.saturating_add(1).clamp(2, MAX_ZOOM)),
         }
     }
 }
 
-#[derive(Resource, Default)]
+/// How a zoomed out cell picks which of the tiles under it to show.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GlyphAggregation {
+    /// Whatever is on top, as if the block were one tile.
+    #[default]
+    HighestZ,
+    /// The glyph covering the most tiles, so terrain reads clearly.
+    MostCommon,
+    /// Whatever has the highest [`ZoomPriority`], so e.g. creatures stay
+    /// visible however far out. Ties go to the highest z.
+    Priority,
+}
+
+/// Importance of a rect when zoomed out with [`GlyphAggregation::Priority`],
+/// rects without one have a priority of 0.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ZoomPriority(pub i32);
+
+/// Which level of a layered world a rect is on, higher is further up.
+///
+/// Cameras show the level their z is on, and optionally a few levels below
+/// it where nothing on their own level is drawn, see
+/// [`TerminalCamera2dSettings::set_look_down`]. Rects without a level show
+/// on every level.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ZLevel(pub i32);
+
+/// Which cameras see a rect, as a mask of up to 32 layers.
+///
+/// Rects without one are on layer 0, which is also all a camera sees unless
+/// given other layers. So a minimap can show only the terrain and markers on
+/// layer 1, while the map shows layer 0 and leaves the markers out.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderLayers(pub u32);
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        Self::layer(0)
+    }
+}
+
+impl RenderLayers {
+    pub const TOTAL: u8 = 32;
+    pub const ALL: RenderLayers = RenderLayers(u32::MAX);
+    pub const NONE: RenderLayers = RenderLayers(0);
+
+    /// Only the given layer.
+    pub const fn layer(n: u8) -> Self {
+        assert!(n < Self::TOTAL, "render layers go from 0 to 31");
+        Self(1 << n)
+    }
+
+    /// Also on the given layer.
+    pub const fn with(self, n: u8) -> Self {
+        Self(self.0 | Self::layer(n).0)
+    }
+
+    pub fn contains(&self, n: u8) -> bool {
+        n < Self::TOTAL && self.0 & (1 << n) != 0
+    }
+
+    /// Whether the two have any layer in common.
+    pub fn intersects(&self, other: &RenderLayers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
+
+/// Looks at the world through a rectangle of the terminal.
+///
+/// Each camera is drawn on its own into its viewport, which hides whatever
+/// cameras of a lower [`order`](Self::order) would have shown there, even
+/// where nothing is in view. Rects in [`ScreenSpace`] are drawn over every
+/// camera.
+#[derive(Component, Default)]
 pub struct TerminalCamera2d {
     dim: Vec2,
     loc: Vec3,
+    zoom: Zoom,
+    /// Part of the terminal drawn to, `None` for all of it.
+    viewport: Option<ScreenSpace>,
+    layers: RenderLayers,
+    order: i32,
     settings: TerminalCamera2dSettings,
 }
 
@@ -61,6 +378,69 @@ impl TerminalCamera2d {
             ..Default::default()
         }
     }
+
+    pub fn with_viewport(mut self, viewport: ScreenSpace) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    pub fn with_layers(mut self, layers: RenderLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn with_zoom(mut self, zoom: Zoom) -> Self {
+        self.zoom = zoom;
+        self
+    }
+
+    pub fn viewport(&self) -> Option<&ScreenSpace> {
+        self.viewport.as_ref()
+    }
+
+    pub fn set_viewport(&mut self, viewport: Option<ScreenSpace>) {
+        self.viewport = viewport;
+    }
+
+    /// The cells of a terminal of the given size this camera draws to.
+    pub fn viewport_rect(&self, screen: UVec2) -> CellRect {
+        let full = CellRect {
+            min: UVec2::ZERO,
+            max: screen,
+        };
+        let Some(viewport) = self.viewport else {
+            return full;
+        };
+        let rect = viewport.resolve(screen.x as u16, screen.y as u16);
+        CellRect {
+            min: rect.min.max(Vec2::ZERO).as_uvec2(),
+            max: rect.max.max(Vec2::ZERO).as_uvec2(),
+        }
+        .intersect(full)
+    }
+
+    pub fn layers(&self) -> RenderLayers {
+        self.layers
+    }
+
+    pub fn set_layers(&mut self, layers: RenderLayers) {
+        self.layers = layers;
+    }
+
+    /// Cameras with a higher order are drawn over those with a lower one,
+    /// ties are broken by entity id.
+    pub fn order(&self) -> i32 {
+        self.order
+    }
+
+    pub fn set_order(&mut self, order: i32) {
+        self.order = order;
+    }
     pub fn width(&self) -> f32 {
         self.dim.x
     }
@@ -103,10 +483,53 @@ impl TerminalCamera2d {
     pub fn set_z(&mut self, z: f32) {
         self.loc.z = z
     }
+
+    /// The [`ZLevel`] in view, the nearest one to the camera's z.
+    pub fn level(&self) -> ZLevel {
+        ZLevel(self.loc.z.round() as i32)
+    }
+
+    pub fn set_level(&mut self, level: ZLevel) {
+        self.loc.z = level.0 as f32;
+    }
+
+    pub fn level_up(&mut self) {
+        self.set_level(ZLevel(self.level().0 + 1));
+    }
+
+    pub fn level_down(&mut self) {
+        self.set_level(ZLevel(self.level().0 - 1));
+    }
     pub fn settings_ref(&self) -> &TerminalCamera2dSettings {
         &self.settings
     }
 
+    pub fn settings_mut(&mut self) -> &mut TerminalCamera2dSettings {
+        &mut self.settings
+    }
+
+    pub fn zoom(&self) -> Zoom {
+        self.zoom
+    }
+
+    pub fn set_zoom(&mut self, zoom: Zoom) {
+        self.zoom = zoom;
+    }
+
+    pub fn zoom_in(&mut self) {
+        self.zoom = self.zoom.zoomed_in();
+    }
+
+    pub fn zoom_out(&mut self) {
+        self.zoom = self.zoom.zoomed_out();
+    }
+
+    /// The area of the world in view, `dim` is in cells so this grows as
+    /// the camera zooms out.
+    pub fn world_rect(&self) -> Rect {
+        Rect::from_center_size(self.loc.truncate(), self.dim * self.zoom.world_per_cell())
+    }
+
     pub fn set_loc(&mut self, loc: Vec3) {
         self.loc = loc;
     }
@@ -122,6 +545,180 @@ impl TerminalCamera2d {
     pub fn set_dim(&mut self, dim: Vec2) {
         self.dim = dim;
     }
+
+    /// How the world maps onto a terminal of the given size in cells.
+    pub fn projection(&self, screen: UVec2) -> CameraProjection {
+        let world = self.world_rect();
+        let viewport = self.viewport_rect(screen);
+        let cell_size = if self.settings.stretch() {
+            world.size() / viewport.size().max(UVec2::ONE).as_vec2()
+        } else {
+            Vec2::splat(self.zoom.world_per_cell())
+        };
+        CameraProjection {
+            world,
+            cell_size,
+            viewport,
+        }
+    }
+
+    /// The world position shown at the center of a terminal cell, given the
+    /// size of the terminal in cells. Cells outside the viewport give
+    /// positions outside the view.
+    pub fn cell_to_world(&self, cell: UVec2, screen: UVec2) -> Vec2 {
+        self.projection(screen).cell_to_world(cell)
+    }
+
+    /// The terminal cell showing a world position, if it's in view.
+    pub fn world_to_cell(&self, world: Vec2, screen: UVec2) -> Option<UVec2> {
+        self.projection(screen).world_to_cell(world)
+    }
+
+    /// The terminal cells showing an area of the world, if any of it is in
+    /// view.
+    pub fn world_rect_to_cells(&self, rect: Rect, screen: UVec2) -> Option<CellRect> {
+        self.projection(screen).world_rect_to_cells(rect)
+    }
+
+    /// Whether any of `rect` is in view, whatever level or layers it's on.
+    pub fn is_in_view(&self, rect: Rect) -> bool {
+        !self.world_rect().intersect(rect).is_empty()
+    }
+
+    /// How far below the camera's level a rect on `level` is, `None` if
+    /// the camera can't see that level. Rects without a level are on every
+    /// level.
+    pub fn levels_below(&self, level: Option<&ZLevel>) -> Option<u8> {
+        levels_below(self.level(), self.settings.look_down(), level)
+    }
+
+    /// Whether the camera draws a rect in the world with the given layers
+    /// and level, if it's in view.
+    pub fn sees(&self, layers: Option<&RenderLayers>, level: Option<&ZLevel>) -> bool {
+        self.layers
+            .intersects(layers.unwrap_or(&RenderLayers::default()))
+            && self.levels_below(level).is_some()
+    }
+}
+
+pub(super) fn levels_below(camera: ZLevel, look_down: u8, level: Option<&ZLevel>) -> Option<u8> {
+    let Some(level) = level else {
+        return Some(0);
+    };
+    let below = camera.0.checked_sub(level.0)?;
+    (0..=look_down as i32)
+        .contains(&below)
+        .then_some(below as u8)
+}
+
+/// Where a camera puts the world on the terminal, see
+/// [`TerminalCamera2d::projection`]. Handy for converting a lot of positions
+/// without laying out the viewport each time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CameraProjection {
+    /// Area of the world in view.
+    pub world: Rect,
+    /// World units covered by each cell.
+    pub cell_size: Vec2,
+    /// Cells of the terminal drawn to.
+    pub viewport: CellRect,
+}
+
+impl CameraProjection {
+    /// The world position shown at the center of `cell`.
+    pub fn cell_to_world(&self, cell: UVec2) -> Vec2 {
+        let local = cell.as_vec2() - self.viewport.min.as_vec2();
+        self.world.min + (local + 0.5) * self.cell_size
+    }
+
+    /// The cell showing `world`, if it's in the viewport.
+    pub fn world_to_cell(&self, world: Vec2) -> Option<UVec2> {
+        let local = ((world - self.world.min) / self.cell_size).floor();
+        if local.x < 0.0 || local.y < 0.0 {
+            return None;
+        }
+        let cell = self.viewport.min + local.as_uvec2();
+        self.viewport.contains(cell).then_some(cell)
+    }
+
+    /// The cells covered by `rect`, clipped to the viewport. A cell counts
+    /// once the rect reaches its left or top edge, `None` if that's none of
+    /// them.
+    pub fn world_rect_to_cells(&self, rect: Rect) -> Option<CellRect> {
+        let overlap = self.world.intersect(rect);
+        if overlap.is_empty() {
+            return None;
+        }
+        let to_cell = |world: Vec2| {
+            self.viewport.min + ((world - self.world.min) / self.cell_size).as_uvec2()
+        };
+        let cells = CellRect {
+            min: to_cell(overlap.min),
+            max: to_cell(overlap.max),
+        }
+        .intersect(self.viewport);
+        (!cells.is_empty()).then_some(cells)
+    }
+}
+
+/// Every camera, for asking what's on screen.
+#[derive(SystemParam)]
+pub struct TerminalCameras<'w, 's> {
+    display_buf: Res<'w, TerminalDisplayBuffer>,
+    cameras: Query<'w, 's, (Entity, &'static TerminalCamera2d)>,
+    rects: Query<'w, 's, PlacementQuery>,
+}
+
+/// Where a rect is drawn, and by which cameras.
+type PlacementQuery = (
+    &'static TextureRect,
+    Option<&'static ScreenSpace>,
+    Option<&'static RenderLayers>,
+    Option<&'static ZLevel>,
+);
+
+impl<'w, 's> TerminalCameras<'w, 's> {
+    /// Size of the terminal in cells.
+    pub fn screen(&self) -> UVec2 {
+        UVec2::new(
+            self.display_buf.0.width as u32,
+            self.display_buf.0.height as u32,
+        )
+    }
+
+    /// The camera drawing to `cell`, the highest order one whose viewport
+    /// covers it.
+    pub fn camera_at(&self, cell: UVec2) -> Option<(Entity, &TerminalCamera2d)> {
+        let screen = self.screen();
+        self.cameras
+            .iter()
+            .filter(|(_, camera)| camera.viewport_rect(screen).contains(cell))
+            .max_by_key(|(entity, camera)| (camera.order(), *entity))
+    }
+
+    /// The world position under `cell`, through whichever camera draws
+    /// there.
+    pub fn cell_to_world(&self, cell: UVec2) -> Option<Vec2> {
+        let (_, camera) = self.camera_at(cell)?;
+        Some(camera.cell_to_world(cell, self.screen()))
+    }
+
+    /// Whether `entity`'s rect is drawn anywhere on the terminal, by any
+    /// camera or in screen space. It may still be covered by something else.
+    pub fn is_on_screen(&self, entity: Entity) -> bool {
+        let Ok((texture, screen_space, layers, level)) = self.rects.get(entity) else {
+            return false;
+        };
+        let rect = Rect::from_center_size(texture.loc, texture.dim);
+        let screen = self.screen();
+        if screen_space.is_some() {
+            let terminal = Rect::from_corners(Vec2::ZERO, screen.as_vec2());
+            return !terminal.intersect(rect).is_empty();
+        }
+        self.cameras.iter().any(|(_, camera)| {
+            camera.sees(layers, level) && camera.world_rect_to_cells(rect, screen).is_some()
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -129,12 +726,17 @@ pub struct TerminalCamera2dSettings {
     /// If enabled, rendering will attempt to stretch objects to fit the screen instead of rending each tile invdidually.
     stretch: bool,
     autoresize: bool,
+    aggregation: GlyphAggregation,
+    /// Levels below the camera's shown through open air.
+    look_down: u8,
 }
 impl Default for TerminalCamera2dSettings {
     fn default() -> Self {
         Self {
             stretch: false,
             autoresize: true,
+            aggregation: GlyphAggregation::default(),
+            look_down: 0,
         }
     }
 }
@@ -155,4 +757,224 @@ impl TerminalCamera2dSettings {
     pub fn stretch(&self) -> bool {
         self.stretch
     }
+
+    pub fn aggregation(&self) -> GlyphAggregation {
+        self.aggregation
+    }
+
+    pub fn set_aggregation(&mut self, aggregation: GlyphAggregation) {
+        self.aggregation = aggregation;
+    }
+
+    pub fn look_down(&self) -> u8 {
+        self.look_down
+    }
+
+    /// Show up to `levels` levels below the camera's wherever nothing on its
+    /// own level is drawn, dimmed so they read as further away. 0 shows
+    /// only the camera's level.
+    pub fn set_look_down(&mut self, levels: u8) {
+        self.look_down = levels;
+    }
+}
+
+#[test]
+fn test_camera_controller() {
+    use super::backend::{HeadlessBackend, Terminal};
+    use super::CellStyle;
+    use bevy::utils::Duration;
+
+    let mut app = App::new();
+    app.insert_resource(Terminal::new(HeadlessBackend::new(10, 4)))
+        .add_plugin(super::TerminalPlugin::default());
+    let rect = |x| TextureRect {
+        texture: '@',
+        style: CellStyle::default(),
+        dim: Vec2::ONE,
+        loc: Vec2::new(x, 0.0),
+        loc_z: 1.0,
+    };
+    let camera = app
+        .world
+        .query_filtered::<Entity, With<MainCamera>>()
+        .single(&app.world);
+    let dwarf = app.world.spawn(rect(0.0)).id();
+    let cat = app.world.spawn(rect(-8.0)).id();
+    let mut millis = 0;
+    let mut step = |app: &mut App, elapsed: u64| {
+        millis += elapsed;
+        let mut time = app.world.resource_mut::<Time>();
+        let now = time.startup() + Duration::from_millis(millis);
+        time.update_with_instant(now);
+        app.update();
+        app.world
+            .get::<TerminalCamera2d>(camera)
+            .unwrap()
+            .loc()
+            .truncate()
+    };
+    app.world
+        .get_mut::<CameraController>(camera)
+        .unwrap()
+        .dead_zone = Vec2::new(2.0, 1.0);
+    app.world
+        .get_mut::<CameraController>(camera)
+        .unwrap()
+        .follow(dwarf);
+    assert_eq!(step(&mut app, 0), Vec2::ZERO);
+
+    // Moving within the dead zone leaves the camera be.
+    app.world.get_mut::<TextureRect>(dwarf).unwrap().loc.x = 2.0;
+    assert_eq!(step(&mut app, 100), Vec2::ZERO);
+    app.world.get_mut::<TextureRect>(dwarf).unwrap().loc.x = 5.0;
+    assert_eq!(step(&mut app, 100), Vec2::new(3.0, 0.0));
+
+    // The 10 wide view can't go past x = 6.
+    app.world
+        .get_mut::<CameraController>(camera)
+        .unwrap()
+        .bounds = Some(Rect::new(-10.0, -5.0, 6.0, 5.0));
+    assert_eq!(step(&mut app, 100), Vec2::new(1.0, 0.0));
+
+    app.world
+        .get_mut::<CameraController>(camera)
+        .unwrap()
+        .jump_to(cat);
+    assert_eq!(step(&mut app, 100), Vec2::new(-5.0, 0.0));
+    let mut controller = app.world.get_mut::<CameraController>(camera).unwrap();
+    assert_eq!(controller.following(), None);
+    assert!(controller.back());
+    assert!(!controller.back());
+    assert_eq!(step(&mut app, 100), Vec2::new(1.0, 0.0));
+
+    // Panning covers the distance over time.
+    let mut controller = app.world.get_mut::<CameraController>(camera).unwrap();
+    controller.pan_speed = Some(10.0);
+    controller.pan_to(Vec2::new(-2.0, 0.0));
+    assert_eq!(step(&mut app, 100), Vec2::new(0.0, 0.0));
+    assert_eq!(step(&mut app, 100), Vec2::new(-1.0, 0.0));
+    assert_eq!(step(&mut app, 500), Vec2::new(-2.0, 0.0));
+    assert_eq!(
+        app.world.get::<CameraController>(camera).unwrap().goal(),
+        None
+    );
+}
+
+#[test]
+fn test_camera_projection() {
+    use super::backend::{HeadlessBackend, Terminal};
+    use super::screen::{Anchor, ScreenLength};
+    use super::CellStyle;
+    use bevy::ecs::system::SystemState;
+
+    let screen = UVec2::new(10, 4);
+    let camera = TerminalCamera2d::new(Vec2::new(10.0, 4.0), Vec3::ZERO);
+    assert_eq!(
+        camera.cell_to_world(UVec2::new(0, 0), screen),
+        Vec2::new(-4.5, -1.5)
+    );
+    assert_eq!(
+        camera.world_to_cell(Vec2::new(0.5, 0.5), screen),
+        Some(UVec2::new(5, 2))
+    );
+    assert_eq!(camera.world_to_cell(Vec2::new(5.0, 0.0), screen), None);
+    assert_eq!(camera.world_to_cell(Vec2::new(-5.5, 0.0), screen), None);
+    assert_eq!(
+        camera.world_rect_to_cells(Rect::new(-10.0, 0.0, 1.0, 1.0), screen),
+        Some(CellRect::new(0, 2, 6, 3))
+    );
+    assert_eq!(
+        camera.world_rect_to_cells(Rect::new(5.0, 0.0, 6.0, 1.0), screen),
+        None
+    );
+
+    // Zoomed out each cell covers more of the world.
+    let camera = camera.with_zoom(Zoom::Out(2));
+    assert_eq!(
+        camera.cell_to_world(UVec2::new(0, 0), screen),
+        Vec2::new(-9.0, -3.0)
+    );
+    assert_eq!(
+        camera.world_to_cell(Vec2::new(-9.5, 3.5), screen),
+        Some(UVec2::new(0, 3))
+    );
+
+    // Stretched to a viewport of half the terminal, offset to its right.
+    let mut camera =
+        TerminalCamera2d::new(Vec2::new(10.0, 4.0), Vec3::ZERO).with_viewport(ScreenSpace::new(
+            Anchor::Right,
+            ScreenLength::Percent(50.0),
+            ScreenLength::Percent(100.0),
+        ));
+    camera.settings_mut().set_stretch(true);
+    let projection = camera.projection(screen);
+    assert_eq!(projection.viewport, CellRect::new(5, 0, 10, 4));
+    assert_eq!(projection.cell_size, Vec2::new(2.0, 1.0));
+    assert_eq!(
+        projection.cell_to_world(UVec2::new(5, 0)),
+        Vec2::new(-4.0, -1.5)
+    );
+    assert_eq!(
+        projection.world_to_cell(Vec2::new(4.5, 1.5)),
+        Some(UVec2::new(9, 3))
+    );
+    for cell in [UVec2::new(5, 0), UVec2::new(7, 1), UVec2::new(9, 3)] {
+        assert_eq!(
+            projection.world_to_cell(projection.cell_to_world(cell)),
+            Some(cell)
+        );
+    }
+
+    // Asking an app what's on screen, through whichever camera draws there.
+    let mut app = App::new();
+    app.insert_resource(Terminal::new(HeadlessBackend::new(10, 4)))
+        .add_plugin(super::TerminalPlugin::default());
+    let rect = |x, y| TextureRect {
+        texture: '@',
+        style: CellStyle::default(),
+        dim: Vec2::ONE,
+        loc: Vec2::new(x, y),
+        loc_z: 0.0,
+    };
+    let dwarf = app.world.spawn(rect(0.5, 0.5)).id();
+    let far = app.world.spawn(rect(50.5, 0.5)).id();
+    let below = app.world.spawn((rect(0.5, 0.5), ZLevel(-1))).id();
+    let hidden = app
+        .world
+        .spawn((rect(0.5, 0.5), RenderLayers::layer(1)))
+        .id();
+    let minimap = app
+        .world
+        .spawn(
+            TerminalCamera2d::new(Vec2::ZERO, Vec3::new(50.0, 0.0, 0.0))
+                .with_viewport(ScreenSpace::new(
+                    Anchor::TopRight,
+                    ScreenLength::Cells(2),
+                    ScreenLength::Cells(2),
+                ))
+                .with_order(1),
+        )
+        .id();
+    app.update();
+    let mut state: SystemState<TerminalCameras> = SystemState::new(&mut app.world);
+    let cameras = state.get(&app.world);
+    assert!(cameras.is_on_screen(dwarf));
+    assert!(cameras.is_on_screen(far));
+    assert!(!cameras.is_on_screen(below));
+    assert!(!cameras.is_on_screen(hidden));
+    assert_eq!(
+        cameras
+            .camera_at(UVec2::new(9, 0))
+            .map(|(entity, _)| entity),
+        Some(minimap)
+    );
+    assert_eq!(
+        cameras.cell_to_world(UVec2::new(9, 1)),
+        Some(Vec2::new(50.5, 0.5))
+    );
+    assert_eq!(
+        cameras.cell_to_world(UVec2::new(5, 2)),
+        Some(Vec2::new(0.5, 0.5))
+    );
+    assert_eq!(cameras.cell_to_world(UVec2::new(10, 0)), None);
 }
@@ -1,16 +1,20 @@
-use std::io::{stdout, StdoutLock, Write};
+use std::io::Write;
 
 use crossterm::cursor::MoveTo;
 use crossterm::queue;
-use crossterm::terminal::{
-    disable_raw_mode, enable_raw_mode, size, BeginSynchronizedUpdate, Clear, ClearType,
-    EndSynchronizedUpdate, EnterAlternateScreen, LeaveAlternateScreen, SetSize,
+use crossterm::style::{
+    Attribute, Attributes, Color, SetAttribute, SetAttributes, SetBackgroundColor,
+    SetForegroundColor,
 };
-use crossterm::{execute, QueueableCommand};
+use crossterm::terminal::{BeginSynchronizedUpdate, Clear, ClearType, EndSynchronizedUpdate};
+use crossterm::QueueableCommand;
+use unicode_width::UnicodeWidthChar;
 
 use crate::prelude::*;
 use crate::util::on_exit::{OnExitPlugin, RegisterOnExit};
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
 
+use super::backend::{self, Terminal};
 use super::input::TerminalResize;
 
 #[derive(Default)]
@@ -18,28 +22,31 @@ pub struct TerminalDisplayPlugin {}
 
 impl Plugin for TerminalDisplayPlugin {
     fn build(&self, app: &mut App) {
+        backend::init_default_backend(app);
+        let display_buffer =
+            TerminalDisplayBuffer::init_from_screen(app.world.resource::<Terminal>());
         app.add_plugin(OnExitPlugin {})
             .add_startup_system(init)
-            .insert_resource(TerminalDisplayBuffer::init_from_screen())
+            .add_startup_system(register_diagnostics)
+            .insert_resource(display_buffer)
+            .init_resource::<TerminalPaintStats>()
             .add_system(handle_terminal_resize)
             .add_system(paint);
     }
 }
 
-fn init(mut onexit_register: EventWriter<RegisterOnExit>) {
-    enable_raw_mode().unwrap();
-    execute!(stdout(), EnterAlternateScreen, crossterm::cursor::Hide,).unwrap();
-
-    onexit_register.send(RegisterOnExit(cleanup));
-}
+pub(super) fn init(
+    mut terminal: ResMut<Terminal>,
+    mut onexit_register: EventWriter<RegisterOnExit>,
+) {
+    terminal.backend_mut().init().unwrap();
 
-fn cleanup() {
-    log::info!("Performing terminal cleanup");
-    disable_raw_mode().unwrap();
-    execute!(stdout(), LeaveAlternateScreen, crossterm::cursor::Show).unwrap();
+    if let Some(cleanup) = terminal.backend().on_exit() {
+        onexit_register.send(RegisterOnExit(cleanup));
+    }
 }
 
-fn handle_terminal_resize(
+pub(super) fn handle_terminal_resize(
     mut term_buffer: ResMut<TerminalDisplayBuffer>,
     mut resize_reader: EventReader<TerminalResize>,
 ) {
@@ -55,42 +62,244 @@ fn handle_terminal_resize(
     }
 }
 
-fn paint_all(term_buffer: &mut ResMut<TerminalDisplayBuffer>) {
-    let mut stdout = stdout().lock();
+fn paint_all(term_buffer: &mut ResMut<TerminalDisplayBuffer>, out: &mut Vec<u8>) {
+    // If we're flushing, clear the backing buffer, this will cause us to reinitialize it and write new data.
+    term_buffer.physical_frame_mut().buf.clear();
+    let (virt, phys) = term_buffer.virt_phys_buffers_mut();
+    paint_all_to(out, virt, phys).unwrap();
+}
+
+/// Full pass repaint, collect values into the physical buffer as we repaint.
+fn paint_all_to(
+    out: &mut impl Write,
+    virt: &VirtualDisplayBuffer,
+    phys: &mut VirtualDisplayBuffer,
+) -> std::io::Result<()> {
     queue!(
-        stdout,
+        out,
         BeginSynchronizedUpdate,
+        SetAttribute(Attribute::Reset),
         MoveTo(0, 0),
         Clear(ClearType::All)
-    )
-    .unwrap();
-    // If we're flushing, clear the backing buffer, this will cause us to reinitialize it and write new data.
-    term_buffer.physical_frame_mut().buf.clear();
-    let (virt, phys) = term_buffer.virt_phys_buffers_mut();
+    )?;
+    let mut style = CellStyle::default();
+    for (idx, cell) in virt.buf.iter().enumerate() {
+        phys.buf.push(*cell);
+        // The wide glyph before us already advanced the cursor over this cell.
+        if !cell.is_continuation() || virt.is_orphan_continuation(idx) {
+            write_cell(out, cell, &mut style)?;
+        }
+    }
+    out.queue(EndSynchronizedUpdate)?.flush()
+}
 
-    // Full pass repaint, collect values into the physical buffer as we repaint.
-    stdout
-        .write(
-            virt.buf
-                .iter()
-                .map(|c| {
-                    phys.buf.push(*c);
-                    *c as u8
-                })
-                .collect::<Vec<u8>>()
-                .as_slice(),
-        )
-        .unwrap();
-    stdout
-        .queue(EndSynchronizedUpdate)
-        .unwrap()
-        .flush()
-        .unwrap();
-}
-
-fn paint(mut term_buffer: ResMut<TerminalDisplayBuffer>) {
+/// Only write the cells which differ between the virtual and physical frames.
+///
+/// We track where the cursor ends up after each write so runs of changed
+/// cells are written back to back, and short gaps between changes on the same
+/// row are filled by rewriting the unchanged cells when that's cheaper than
+/// moving the cursor over them.
+fn paint_diff_to(
+    out: &mut impl Write,
+    virt: &VirtualDisplayBuffer,
+    phys: &mut VirtualDisplayBuffer,
+) -> std::io::Result<()> {
+    let width = virt.width as usize;
+    queue!(
+        out,
+        BeginSynchronizedUpdate,
+        SetAttribute(Attribute::Reset),
+        // I don't know what this would actually do.. won't bother enabling for now.
+        //SetSize(width, height),
+    )?;
+    let dirty = dirty_cells(virt, phys);
+    let mut style = CellStyle::default();
+    // Index of the cell the cursor is sitting on, if we know it.
+    let mut cursor: Option<usize> = None;
+    let mut gap = Vec::new();
+    // Now just iterate, write in only changes...
+    for (idx, v_c) in virt.buf.iter().enumerate() {
+        if !dirty[idx] {
+            continue;
+        }
+        // Update phys buffer
+        phys.buf[idx] = *v_c;
+        if v_c.is_continuation() && !virt.is_orphan_continuation(idx) {
+            // Written along with the wide glyph before it.
+            continue;
+        }
+        let col = idx % width;
+        let row = idx / width;
+        if cursor != Some(idx) {
+            let move_to = MoveTo(col as u16, row as u16);
+            let move_len = move_to_len(move_to);
+            let filled = match cursor {
+                Some(from) if from < idx && from / width == row => {
+                    gap.clear();
+                    let mut gap_style = style;
+                    encode_cells(&mut gap, virt, from..idx, &mut gap_style, move_len)?;
+                    let cheaper = gap.len() < move_len;
+                    if cheaper {
+                        out.write_all(&gap)?;
+                        style = gap_style;
+                    }
+                    cheaper
+                }
+                _ => false,
+            };
+            if !filled {
+                out.queue(move_to)?;
+            }
+        }
+        write_cell(out, v_c, &mut style)?;
+        let next = col + v_c.width().max(1) as usize;
+        // Writing into the last column leaves the cursor in a pending wrap
+        // state which differs between terminals, don't rely on it.
+        cursor = (next < width).then_some(idx + next - col);
+    }
+    out.queue(EndSynchronizedUpdate)?.flush()
+}
+
+/// Write the cells in `range`, giving up once more than `limit` bytes were written.
+fn encode_cells(
+    out: &mut Vec<u8>,
+    virt: &VirtualDisplayBuffer,
+    range: std::ops::Range<usize>,
+    style: &mut CellStyle,
+    limit: usize,
+) -> std::io::Result<()> {
+    for idx in range {
+        let cell = &virt.buf[idx];
+        if !cell.is_continuation() || virt.is_orphan_continuation(idx) {
+            write_cell(out, cell, style)?;
+        }
+        if out.len() > limit {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Number of bytes a `MoveTo` is encoded as: `ESC [ row ; col H`.
+fn move_to_len(move_to: MoveTo) -> usize {
+    let digits = |n: u16| (n as u32 + 1).ilog10() as usize + 1;
+    4 + digits(move_to.0) + digits(move_to.1)
+}
+
+/// Encode the next frame into `out`. Normally this is just the difference
+/// between the frames, but if that's estimated to cost more than repainting
+/// everything we do a full repaint instead. Returns whether a full repaint
+/// was used.
+fn encode_frame(
+    out: &mut Vec<u8>,
+    scratch: &mut Vec<u8>,
+    virt: &VirtualDisplayBuffer,
+    phys: &mut VirtualDisplayBuffer,
+) -> std::io::Result<bool> {
+    paint_diff_to(out, virt, phys)?;
+    // A full repaint costs at least a byte per cell, only bother encoding one
+    // if it could possibly be cheaper.
+    if out.len() <= virt.buf.len() {
+        return Ok(false);
+    }
+    scratch.clear();
+    phys.buf.clear();
+    paint_all_to(scratch, virt, phys)?;
+    if scratch.len() < out.len() {
+        std::mem::swap(out, scratch);
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Find the cells which need to be repainted.
+///
+/// Terminals treat both halves of a wide glyph as a unit, writing over either
+/// half erases the whole glyph. So if any half of a wide glyph changed (in
+/// either frame) we need to repaint both halves.
+fn dirty_cells(virt: &VirtualDisplayBuffer, phys: &VirtualDisplayBuffer) -> Vec<bool> {
+    let mut dirty: Vec<bool> = virt
+        .buf
+        .iter()
+        .zip(phys.buf.iter())
+        .map(|(v_c, p_c)| v_c != p_c)
+        .collect();
+    let width = virt.width as usize;
+    for idx in 0..dirty.len() {
+        if !dirty[idx] {
+            continue;
+        }
+        let col = idx % width;
+        let (v_c, p_c) = (&virt.buf[idx], &phys.buf[idx]);
+        if col > 0 && (v_c.is_continuation() || p_c.is_continuation()) {
+            dirty[idx - 1] = true;
+        }
+        if col + 1 < width && (v_c.width() > 1 || p_c.width() > 1) {
+            dirty[idx + 1] = true;
+        }
+    }
+    dirty
+}
+
+/// Write a single cell, only emitting style commands for the parts of the
+/// style which differ from what the terminal currently has set.
+fn write_cell(out: &mut impl Write, cell: &Cell, current: &mut CellStyle) -> std::io::Result<()> {
+    let style = &cell.style;
+    if style.attrs != current.attrs {
+        // There's no portable way to unset a single attribute, so reset them
+        // all. This also resets the colors, so they need to be re-applied.
+        out.queue(SetAttribute(Attribute::Reset))?;
+        if !style.attrs.is_empty() {
+            out.queue(SetAttributes(style.attrs))?;
+        }
+        *current = CellStyle {
+            attrs: style.attrs,
+            ..Default::default()
+        };
+    }
+    if style.fg != current.fg {
+        out.queue(SetForegroundColor(style.fg))?;
+        current.fg = style.fg;
+    }
+    if style.bg != current.bg {
+        out.queue(SetBackgroundColor(style.bg))?;
+        current.bg = style.bg;
+    }
+    let mut utf8 = [0u8; 4];
+    out.write_all(cell.printable_glyph().encode_utf8(&mut utf8).as_bytes())
+}
+
+/// Output written to the terminal, updated every frame.
+#[derive(Resource, Default, Debug)]
+pub struct TerminalPaintStats {
+    /// Bytes written to the terminal during the last frame.
+    pub frame_bytes: usize,
+    /// Whether the last frame was a full repaint rather than a partial one.
+    pub full_repaint: bool,
+    /// Bytes written to the terminal since startup.
+    pub total_bytes: u64,
+}
+
+pub const PAINT_BYTES: DiagnosticId =
+    DiagnosticId::from_u128(250463853186412794616357934925316081029);
+
+fn register_diagnostics(diagnostics: Option<ResMut<Diagnostics>>) {
+    if let Some(mut diagnostics) = diagnostics {
+        diagnostics.add(Diagnostic::new(PAINT_BYTES, "terminal_paint_bytes", 20).with_suffix("B"));
+    }
+}
+
+pub(super) fn paint(
+    mut term_buffer: ResMut<TerminalDisplayBuffer>,
+    mut terminal: ResMut<Terminal>,
+    mut stats: ResMut<TerminalPaintStats>,
+    diagnostics: Option<ResMut<Diagnostics>>,
+    mut frame: Local<Vec<u8>>,
+    mut scratch: Local<Vec<u8>>,
+) {
+    stats.frame_bytes = 0;
+    stats.full_repaint = false;
     // Detect if there's an update.
-    // If so, perform the render. (TODO: Maybe only render part if necessary?)
     if term_buffer.is_changed() {
         log::info!("Change detected");
         //for (i, c) in term_buffer.0.buf.iter().enumerate() {
@@ -101,7 +310,7 @@ fn paint(mut term_buffer: ResMut<TerminalDisplayBuffer>) {
         //
         // For now, let's just check if  the dimmensions look like they're gonna be fkd and log a warning, we can updated/fix in the next pass.
         if cfg!(debug_assertions) {
-            let (width, height) = get_term_size();
+            let (width, height) = terminal.backend().size().unwrap();
             if (width, height) != (term_buffer.0.width, term_buffer.0.height) {
                 log::warn!(
                     "Write buffer size: {:?} doesn't match current terminal size: {:?}",
@@ -119,62 +328,142 @@ fn paint(mut term_buffer: ResMut<TerminalDisplayBuffer>) {
             term_buffer.0.width as usize * term_buffer.0.height as usize
         );
 
+        frame.clear();
         if term_buffer.get_flush() {
             log::info!("Performing full flush paint.");
-            paint_all(&mut term_buffer);
+            paint_all(&mut term_buffer, &mut frame);
             term_buffer.set_flush(false);
-            return;
+            stats.full_repaint = true;
+        } else {
+            let (virt, phys) = term_buffer.virt_phys_buffers_ref();
+            if virt.buf != phys.buf {
+                let (virt, phys) = term_buffer.virt_phys_buffers_mut();
+                log::info!("Painting!");
+                stats.full_repaint = encode_frame(&mut frame, &mut scratch, virt, phys).unwrap();
+            }
         }
 
-        let (virt, phys) = term_buffer.virt_phys_buffers_ref();
-        if virt.buf == phys.buf {
-            return;
+        if !frame.is_empty() {
+            let writer = terminal.backend_mut().writer();
+            writer.write_all(&frame).unwrap();
+            writer.flush().unwrap();
         }
+        stats.frame_bytes = frame.len();
+        stats.total_bytes += frame.len() as u64;
+    }
 
-        let (virt, phys) = term_buffer.virt_phys_buffers_mut();
-        let width = virt.width;
-        let height = virt.height;
-
-        let mut stdout = stdout().lock();
-        log::info!("Painting!");
-        queue!(
-            stdout,
-            BeginSynchronizedUpdate,
-            MoveTo(0, 0),
-            // I don't know what this would actually do.. won't bother enabling for now.
-            //SetSize(width, height),
-        )
-        .unwrap();
-        // Now just iterate, write in only changes...
-        for (idx, (v_c, p_c_mut)) in virt.buf.iter().zip(phys.buf.iter_mut()).enumerate() {
-            if *v_c != *p_c_mut {
-                let col = idx % width as usize;
-                let row = idx / width as usize;
-                // Move cursor and write
-                stdout
-                    .queue(MoveTo(col as u16, row as u16))
-                    .unwrap()
-                    .write(&[*v_c as u8]);
-                // Update phys buffer
-                *p_c_mut = *v_c;
-            }
+    if let Some(mut diagnostics) = diagnostics {
+        diagnostics.add_measurement(PAINT_BYTES, || stats.frame_bytes as f64);
+    }
+}
+
+/// Colors and attributes applied to a single cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CellStyle {
+    pub fg: Color,
+    pub bg: Color,
+    pub attrs: Attributes,
+}
+
+impl Default for CellStyle {
+    fn default() -> Self {
+        Self {
+            fg: Color::Reset,
+            bg: Color::Reset,
+            attrs: Attributes::default(),
+        }
+    }
+}
+
+impl CellStyle {
+    pub fn new(fg: Color, bg: Color) -> Self {
+        Self {
+            fg,
+            bg,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_fg(mut self, fg: Color) -> Self {
+        self.fg = fg;
+        self
+    }
+
+    pub fn with_bg(mut self, bg: Color) -> Self {
+        self.bg = bg;
+        self
+    }
+
+    pub fn with_attribute(mut self, attr: Attribute) -> Self {
+        self.attrs.set(attr);
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub glyph: char,
+    pub style: CellStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            glyph: ' ',
+            style: CellStyle::default(),
         }
-        stdout
-            .queue(EndSynchronizedUpdate)
-            .unwrap()
-            .flush()
-            .unwrap();
     }
 }
 
-fn get_term_size() -> (u16, u16) {
-    size().unwrap()
+impl Cell {
+    /// Marker glyph for the right half of a wide glyph, these cells are never
+    /// written themselves, the glyph to their left covers them.
+    pub const CONTINUATION: char = '\0';
+
+    pub fn new(glyph: char, style: CellStyle) -> Self {
+        Self { glyph, style }
+    }
+
+    pub fn continuation(style: CellStyle) -> Self {
+        Self {
+            glyph: Self::CONTINUATION,
+            style,
+        }
+    }
+
+    pub fn is_continuation(&self) -> bool {
+        self.glyph == Self::CONTINUATION
+    }
+
+    /// Number of columns this cell's glyph takes up on the terminal.
+    pub fn width(&self) -> u16 {
+        if self.is_continuation() {
+            return 0;
+        }
+        glyph_width(self.glyph)
+    }
+
+    /// The glyph we'll actually write, control and zero width characters
+    /// would desync our cursor tracking so we swap them for a space.
+    fn printable_glyph(&self) -> char {
+        match self.glyph.width() {
+            Some(1) | Some(2) => self.glyph,
+            _ => ' ',
+        }
+    }
+}
+
+/// Number of terminal columns `glyph` occupies, either 1 or 2.
+pub fn glyph_width(glyph: char) -> u16 {
+    match glyph.width() {
+        Some(2) => 2,
+        _ => 1,
+    }
 }
 
 #[derive(Clone)]
 pub struct VirtualDisplayBuffer {
-    // Currently we only support ascii and uncolored... Likely will change.
-    pub buf: Vec<char>,
+    pub buf: Vec<Cell>,
     pub width: u16,
     pub height: u16,
 }
@@ -184,7 +473,56 @@ impl VirtualDisplayBuffer {
         self.width = width;
         self.height = height;
         self.buf.clear();
-        self.buf.resize((width * height) as usize, ' ');
+        self.buf.resize((width * height) as usize, Cell::default());
+    }
+
+    pub fn get(&self, col: u16, row: u16) -> Option<&Cell> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+        self.buf
+            .get(col as usize + row as usize * self.width as usize)
+    }
+
+    /// Write a cell, keeping wide glyphs consistent.
+    ///
+    /// Wide glyphs also claim the cell to their right (marking it as a
+    /// continuation), and any wide glyph which gets partially overwritten is
+    /// blanked out. A wide glyph which doesn't fit in the last column is
+    /// replaced by a space.
+    pub fn set(&mut self, col: u16, row: u16, cell: Cell) {
+        if col >= self.width || row >= self.height {
+            return;
+        }
+        let idx = col as usize + row as usize * self.width as usize;
+        self.break_wide_at(idx);
+        if cell.width() > 1 {
+            if col + 1 >= self.width {
+                self.buf[idx] = Cell::new(' ', cell.style);
+                return;
+            }
+            self.break_wide_at(idx + 1);
+            self.buf[idx + 1] = Cell::continuation(cell.style);
+        }
+        self.buf[idx] = cell;
+    }
+
+    /// Blank out the other half of any wide glyph occupying `idx`.
+    fn break_wide_at(&mut self, idx: usize) {
+        let col = idx % self.width as usize;
+        let cell = self.buf[idx];
+        if cell.is_continuation() && col > 0 {
+            self.buf[idx - 1] = Cell::new(' ', self.buf[idx - 1].style);
+        } else if cell.width() > 1 && col + 1 < self.width as usize {
+            self.buf[idx + 1] = Cell::new(' ', self.buf[idx + 1].style);
+        }
+    }
+
+    /// A continuation cell which isn't preceded by a wide glyph, i.e. someone
+    /// wrote into `buf` directly. We paint these as blanks.
+    fn is_orphan_continuation(&self, idx: usize) -> bool {
+        let col = idx % self.width as usize;
+        col == 0 || self.buf[idx - 1].width() < 2
     }
 }
 
@@ -197,11 +535,11 @@ pub struct TerminalDisplayBuffer(
     bool,
 );
 impl TerminalDisplayBuffer {
-    fn init_from_screen() -> Self {
-        let (width, height) = get_term_size();
+    fn init_from_screen(terminal: &Terminal) -> Self {
+        let (width, height) = terminal.backend().size().unwrap();
         log::info!("w,h: {:?},{:?}", width, height);
         let buf = VirtualDisplayBuffer {
-            buf: vec!['\0'; width as usize * height as usize],
+            buf: vec![Cell::default(); width as usize * height as usize],
             width,
             height,
         };
@@ -243,3 +581,129 @@ impl TerminalDisplayBuffer {
         &self.0
     }
 }
+
+#[test]
+fn test_paint_emits_style_only_on_change() {
+    let red = CellStyle::new(Color::Red, Color::Reset);
+    let blue = CellStyle::new(Color::Blue, Color::Reset).with_attribute(Attribute::Bold);
+    let virt = VirtualDisplayBuffer {
+        buf: vec![
+            Cell::new('a', red),
+            Cell::new('b', red),
+            Cell::new('c', blue),
+            Cell::new('d', CellStyle::default()),
+        ],
+        width: 4,
+        height: 1,
+    };
+    let mut phys = VirtualDisplayBuffer {
+        buf: vec![],
+        width: 4,
+        height: 1,
+    };
+    let mut out = Vec::new();
+    paint_all_to(&mut out, &virt, &mut phys).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    let set_red = SetForegroundColor(Color::Red).to_string();
+    let set_blue = SetForegroundColor(Color::Blue).to_string();
+    let set_bold = SetAttribute(Attribute::Bold).to_string();
+    assert_eq!(out.matches(&set_red).count(), 1);
+    assert_eq!(out.matches(&set_blue).count(), 1);
+    assert_eq!(out.matches(&set_bold).count(), 1);
+    assert!(out.contains(&format!("{}ab", set_red)));
+    assert!(out.contains(&format!("{}c", set_blue)));
+    assert_eq!(phys.buf, virt.buf);
+
+    // Repainting only the changed cell should only restate the style it needs.
+    let mut virt = virt;
+    virt.buf[1] = Cell::new('x', red);
+    let mut out = Vec::new();
+    paint_diff_to(&mut out, &virt, &mut phys).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(&format!("{}{}x", MoveTo(1, 0), set_red)));
+    assert!(!out.contains(&set_blue));
+    assert_eq!(phys.buf, virt.buf);
+}
+
+#[test]
+fn test_paint_wide_glyphs() {
+    let mut virt = VirtualDisplayBuffer {
+        buf: vec![],
+        width: 4,
+        height: 1,
+    };
+    virt.resize(4, 1);
+    for (glyph, col) in ['a', '字', 'b'].into_iter().zip([0, 1, 3]) {
+        virt.set(col, 0, Cell::new(glyph, CellStyle::default()));
+    }
+    assert!(virt.buf[2].is_continuation());
+
+    let mut phys = virt.clone();
+    phys.buf.clear();
+    let mut out = Vec::new();
+    paint_all_to(&mut out, &virt, &mut phys).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("a字b"));
+
+    // Overwriting the continuation half blanks out the wide glyph, both
+    // halves need to be repainted.
+    virt.set(2, 0, Cell::new('y', CellStyle::default()));
+    assert_eq!(virt.buf[1].glyph, ' ');
+    let mut out = Vec::new();
+    paint_diff_to(&mut out, &virt, &mut phys).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(&format!("{} y", MoveTo(1, 0))));
+    assert!(!out.contains('字'));
+    assert_eq!(phys.buf, virt.buf);
+
+    // A wide glyph over narrow ones only writes the lead cell.
+    virt.set(1, 0, Cell::new('字', CellStyle::default()));
+    let mut out = Vec::new();
+    paint_diff_to(&mut out, &virt, &mut phys).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(&format!("{}字", MoveTo(1, 0))));
+    assert!(!out.contains(&MoveTo(2, 0).to_string()));
+    assert_eq!(phys.buf, virt.buf);
+
+    // No room for a wide glyph in the last column.
+    virt.set(3, 0, Cell::new('字', CellStyle::default()));
+    assert_eq!(virt.buf[3].glyph, ' ');
+}
+
+#[test]
+fn test_paint_diff_batches_runs() {
+    let mut virt = VirtualDisplayBuffer {
+        buf: vec![],
+        width: 40,
+        height: 2,
+    };
+    virt.resize(40, 2);
+    let mut phys = virt.clone();
+    for (col, glyph) in "abc".chars().enumerate() {
+        virt.set(col as u16 + 10, 0, Cell::new(glyph, CellStyle::default()));
+    }
+    // Close enough that rewriting the unchanged gap beats a MoveTo.
+    virt.set(15, 0, Cell::new('d', CellStyle::default()));
+    // Far enough that it doesn't.
+    virt.set(35, 0, Cell::new('e', CellStyle::default()));
+
+    let mut out = Vec::new();
+    paint_diff_to(&mut out, &virt, &mut phys).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(&format!("{}abc  d{}e", MoveTo(10, 0), MoveTo(35, 0))));
+    assert_eq!(out.matches('\x1b').count(), 5);
+    assert_eq!(phys.buf, virt.buf);
+
+    // Changing nearly every cell is cheaper to send as a full repaint.
+    for idx in 0..virt.buf.len() {
+        virt.buf[idx] = Cell::new(
+            'x',
+            CellStyle::new(Color::AnsiValue(idx as u8), Color::Reset),
+        );
+    }
+    let (mut out, mut scratch) = (Vec::new(), Vec::new());
+    assert!(encode_frame(&mut out, &mut scratch, &virt, &mut phys).unwrap());
+    assert!(out.len() < scratch.len());
+    assert_eq!(phys.buf, virt.buf);
+}
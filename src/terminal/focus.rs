@@ -0,0 +1,242 @@
+use bevy::input::keyboard::{ButtonState, KeyboardInput};
+
+use crate::prelude::*;
+
+use super::input::{self, TerminalKeyInput};
+use super::render::TextureRect;
+use super::widget::Widget;
+
+#[derive(Default)]
+pub struct TerminalFocusPlugin();
+
+impl Plugin for TerminalFocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FocusStack>()
+            .add_event::<FocusedInput>()
+            .add_system(prune_focus_stack.before(route_input))
+            .add_system(route_input.after(input::handle_input_buffer));
+    }
+}
+
+/// Who keyboard input goes to.
+///
+/// Menus, text fields or anything else wanting the keyboard push themselves
+/// on top and pop themselves off when they're done, only the top of the stack
+/// gets input. While the stack is empty input goes to the map view and global
+/// shortcuts. Despawned entities are dropped from the stack.
+#[derive(Resource, Default, Debug, Clone, PartialEq, Eq)]
+pub struct FocusStack {
+    stack: Vec<Entity>,
+}
+
+impl FocusStack {
+    /// Give focus to `entity`, moving it to the top if it's already in the
+    /// stack.
+    pub fn push(&mut self, entity: Entity) {
+        self.remove(entity);
+        self.stack.push(entity);
+    }
+
+    pub fn pop(&mut self) -> Option<Entity> {
+        self.stack.pop()
+    }
+
+    /// Swap whatever is focused for `entity`, pushing it if nothing is.
+    pub fn replace_top(&mut self, entity: Entity) {
+        self.stack.pop();
+        self.push(entity);
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        self.stack.retain(|e| *e != entity);
+    }
+
+    /// The entity with focus.
+    pub fn top(&self) -> Option<Entity> {
+        self.stack.last().copied()
+    }
+
+    pub fn is_focused(&self, entity: Entity) -> bool {
+        self.top() == Some(entity)
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.stack.contains(&entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+}
+
+/// Keys and typed characters for whoever is on top of the [`FocusStack`],
+/// in the order they arrived. `target` is `None` when nothing has focus.
+#[derive(Debug, Clone)]
+pub struct FocusedInput {
+    pub target: Option<Entity>,
+    pub input: TerminalKeyInput,
+}
+
+fn prune_focus_stack(mut focus: ResMut<FocusStack>, entities: Query<()>) {
+    // Don't flag the stack as changed unless something was actually dropped.
+    if focus
+        .bypass_change_detection()
+        .stack
+        .iter()
+        .any(|entity| entities.get(*entity).is_err())
+    {
+        focus.stack.retain(|entity| entities.get(*entity).is_ok());
+    }
+}
+
+pub(super) fn route_input(
+    mut input: EventReader<TerminalKeyInput>,
+    mut focus: ResMut<FocusStack>,
+    mut shift: Local<bool>,
+    widgets: Query<(Entity, &Widget, &TextureRect)>,
+    parents: Query<&Parent>,
+    mut writer: EventWriter<FocusedInput>,
+) {
+    for event in input.iter() {
+        if let TerminalKeyInput::Key(key) = event {
+            match key.key_code {
+                Some(KeyCode::LShift | KeyCode::RShift) => {
+                    *shift = key.state == ButtonState::Pressed;
+                }
+                // Tab moves between the widgets of whichever menu has focus.
+                Some(KeyCode::Tab) if matches!(focus.top(), Some(top) if widgets.contains(top)) => {
+                    if key.state == ButtonState::Pressed {
+                        let top = focus.top().unwrap();
+                        if let Some(next) = cycle_widgets(top, !*shift, &widgets, &parents) {
+                            focus.replace_top(next);
+                        }
+                    }
+                    continue;
+                }
+                _ => (),
+            }
+        }
+        writer.send(FocusedInput {
+            target: focus.top(),
+            input: *event,
+        });
+    }
+}
+
+/// The next focusable widget after `current` in reading order, among the
+/// widgets which share its root.
+fn cycle_widgets(
+    current: Entity,
+    forward: bool,
+    widgets: &Query<(Entity, &Widget, &TextureRect)>,
+    parents: &Query<&Parent>,
+) -> Option<Entity> {
+    let root = |mut entity: Entity| {
+        while let Ok(parent) = parents.get(entity) {
+            entity = parent.get();
+        }
+        entity
+    };
+    let current_root = root(current);
+    let mut candidates: Vec<(Entity, Vec2)> = widgets
+        .iter()
+        .filter(|(entity, widget, _)| {
+            (*entity == current || widget.is_focusable()) && root(*entity) == current_root
+        })
+        .map(|(entity, _, texture)| (entity, texture.loc - texture.dim / 2.0))
+        .collect();
+    candidates.sort_by(|(a, a_loc), (b, b_loc)| {
+        (a_loc.y, a_loc.x)
+            .partial_cmp(&(b_loc.y, b_loc.x))
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then(a.cmp(b))
+    });
+    let idx = candidates
+        .iter()
+        .position(|(entity, _)| *entity == current)?;
+    let len = candidates.len();
+    let next = if forward {
+        (idx + 1) % len
+    } else {
+        (idx + len - 1) % len
+    };
+    Some(candidates[next].0)
+}
+
+#[test]
+fn test_focus_routing() {
+    use super::backend::{HeadlessBackend, Terminal};
+    use super::widget::WidgetBundle;
+
+    let mut app = App::new();
+    app.insert_resource(Terminal::new(HeadlessBackend::new(20, 6)))
+        .add_plugin(super::TerminalPlugin::default());
+    let menu = app
+        .world
+        .spawn(WidgetBundle::new(Widget::panel("Menu")))
+        .id();
+    let first = app
+        .world
+        .spawn(WidgetBundle::new(Widget::button("One")))
+        .id();
+    let label = app
+        .world
+        .spawn(WidgetBundle::new(Widget::label("Not focusable")))
+        .id();
+    let second = app
+        .world
+        .spawn(WidgetBundle::new(Widget::button("Two")))
+        .id();
+    app.world
+        .entity_mut(menu)
+        .push_children(&[first, label, second]);
+    app.update();
+
+    let press = |app: &mut App, key| {
+        app.world.resource_mut::<Events<FocusedInput>>().clear();
+        for state in [ButtonState::Pressed, ButtonState::Released] {
+            app.world.send_event(TerminalKeyInput::Key(KeyboardInput {
+                scan_code: 0,
+                key_code: Some(key),
+                state,
+            }));
+        }
+        app.update();
+        let events = app.world.resource::<Events<FocusedInput>>();
+        events
+            .get_reader()
+            .iter(events)
+            .map(|event| event.target)
+            .last()
+    };
+
+    // Nothing has focus, input goes to the map.
+    assert_eq!(press(&mut app, KeyCode::Q), Some(None));
+
+    app.world.resource_mut::<FocusStack>().push(first);
+    assert_eq!(press(&mut app, KeyCode::Q), Some(Some(first)));
+    assert_eq!(press(&mut app, KeyCode::Tab), None);
+    assert!(app.world.resource::<FocusStack>().is_focused(second));
+    press(&mut app, KeyCode::Tab);
+    assert!(app.world.resource::<FocusStack>().is_focused(first));
+
+    // Shift+Tab goes backwards.
+    app.world.send_event(TerminalKeyInput::Key(KeyboardInput {
+        scan_code: 0,
+        key_code: Some(KeyCode::LShift),
+        state: ButtonState::Pressed,
+    }));
+    press(&mut app, KeyCode::Tab);
+    assert!(app.world.resource::<FocusStack>().is_focused(second));
+
+    app.world.despawn(menu);
+    app.world.despawn(first);
+    app.world.despawn(label);
+    app.world.despawn(second);
+    app.update();
+    assert!(app.world.resource::<FocusStack>().is_empty());
+}
@@ -1,96 +1,440 @@
 use bevy::app::AppExit;
+use bevy::ecs::system::SystemParam;
+use bevy::utils::Duration;
 
 use crate::prelude::*;
-use crossterm::event::{poll, read, Event, KeyEvent};
-use std::collections::VecDeque;
-use std::sync::Mutex;
+use crossterm::event::Event;
 
 use bevy::input::keyboard::KeyCode as BevyKeyCode;
 use bevy::input::keyboard::{ButtonState, KeyboardInput};
-use crossterm::event::KeyCode;
-use once_cell::sync::Lazy;
+use bevy::input::mouse::{MouseButton, MouseButtonInput, MouseScrollUnit, MouseWheel};
+use crossterm::event::{
+    KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MediaKeyCode, ModifierKeyCode,
+    MouseButton as CrosstermMouseButton, MouseEventKind,
+};
 
-use crate::util::on_exit::RegisterOnExit;
+use super::action::{self, ActionEvent};
+use super::backend::{self, Terminal};
+use super::display;
+use crate::util::on_exit;
 
 #[derive(Default)]
 pub struct TerminalInputPlugin {}
-use std::thread::JoinHandle;
 
 impl Plugin for TerminalInputPlugin {
     fn build(&self, app: &mut App) {
-        app.add_event::<KeyboardInput>()
+        backend::init_default_backend(app);
+        app.init_resource::<Time>()
+            .init_resource::<KeyHoldTimeout>()
+            .init_resource::<Input<BevyKeyCode>>()
+            .add_event::<KeyboardInput>()
             .add_event::<TerminalResize>()
+            .add_event::<ReceivedCharacter>()
+            .add_event::<TerminalKeyInput>()
+            .add_event::<MouseButtonInput>()
+            .add_event::<MouseWheel>()
+            .add_event::<TerminalMouseButton>()
+            .add_event::<TerminalMouseWheel>()
+            .add_event::<TerminalCursorMoved>()
+            .add_event::<TerminalPaste>()
+            .add_event::<TerminalFocusChanged>()
+            .add_event::<TerminalInputError>()
             .add_system(handle_input_buffer)
-            .add_system(escape_listener)
-            .add_startup_system(init);
+            .add_system(update_key_input.after(handle_input_buffer))
+            .add_system(quit_listener.after(action::trigger_actions))
+            .add_startup_system(init.after(display::init))
+            .add_system(
+                stop_input
+                    .in_base_set(CoreSet::Last)
+                    .before(on_exit::handle_app_exit),
+            );
     }
 }
 
+#[derive(Debug, Default, Clone)]
+pub struct TerminalResize {
+    pub width: u16,
+    pub height: u16,
+}
+
+/// A character typed or pasted, as it would appear on screen.
+///
+/// Unlike [`KeyboardInput`] this keeps the case, symbols and letters which
+/// have no [`KeyCode`](BevyKeyCode) of their own, use it for anything
+/// accepting text. Chords with Ctrl or Alt aren't text and only show up as
+/// keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReceivedCharacter {
+    pub char: char,
+}
+
+/// Keys and typed characters together, in the order they arrived.
+///
+/// The same input is sent as [`KeyboardInput`] and [`ReceivedCharacter`],
+/// but events of different types can't be ordered against each other. Text
+/// fields need this order so typing "ab" then Backspace deletes the "b".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TerminalKeyInput {
+    Key(KeyboardInput),
+    Char(char),
+}
+
+/// Text pasted into the terminal, all in one go.
+///
+/// The text is also sent as [`ReceivedCharacter`]s, so text fields take
+/// pastes without looking for these.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalPaste(pub String);
+
+/// The terminal gained (`true`) or lost (`false`) focus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalFocusChanged(pub bool);
+
+/// Reading the terminal's input failed, no more input will arrive.
+#[derive(Debug)]
+pub struct TerminalInputError(pub std::io::Error);
+
+/// The mouse moved to another terminal cell, (0, 0) is the top left.
+///
+/// Use [`TerminalCameras::cell_to_world`] to find what's under it.
+///
+/// [`TerminalCameras::cell_to_world`]: super::camera::TerminalCameras::cell_to_world
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalCursorMoved {
+    pub position: UVec2,
+}
+
+/// A mouse button was pressed or released over the terminal cell at
+/// `position`.
+///
+/// Also sent as a [`MouseButtonInput`], which doesn't say where it happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerminalMouseButton {
+    pub button: MouseButton,
+    pub state: ButtonState,
+    pub position: UVec2,
+}
+
+/// The mouse wheel scrolled over the terminal cell at `position`, `delta` is
+/// in lines with up being positive.
+///
+/// Also sent as a [`MouseWheel`], which doesn't say where it happened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerminalMouseWheel {
+    pub delta: Vec2,
+    pub position: UVec2,
+}
+
+/// How long a key counts as held after the terminal last reported it.
+///
+/// Terminals without keyboard enhancement never say when a key is released,
+/// they only repeat presses while it's held. Keys are released once they
+/// haven't been repeated for this long, or as soon as another key is pressed
+/// (only the newest key repeats).
+///
+/// This has to be longer than the delay before the terminal starts repeating,
+/// which comes from the OS keyboard settings (660ms by default on X11), or
+/// held keys flicker released and pressed again. Insert the resource with a
+/// longer timeout for users with a slower repeat delay.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyHoldTimeout(pub Duration);
+
+impl Default for KeyHoldTimeout {
+    fn default() -> Self {
+        Self(Duration::from_millis(750))
+    }
+}
+
+/// Keys and modifiers we've sent presses for without a release yet.
 #[derive(Default)]
-struct TerminalState {
-    handle: Option<JoinHandle<()>>,
-    key_buffer: VecDeque<KeyEvent>,
-    resize: Option<TerminalResize>,
-}
-
-fn input_thread_loop() {
-    loop {
-        if poll(std::time::Duration::from_millis(500)).unwrap() {
-            // It's guaranteed that the `read()` won't block when the `poll()` function returns `true`
-            match read().unwrap() {
-                Event::Key(event) => INPUT_THREAD_BUF
-                    .lock()
-                    .unwrap()
-                    .key_buffer
-                    .push_front(event),
-                Event::Resize(width, height) => {
-                    INPUT_THREAD_BUF.lock().unwrap().resize = Some(TerminalResize { width, height })
+pub(super) struct HeldKeys {
+    /// When each key was last pressed or repeated.
+    keys: Vec<(BevyKeyCode, Duration)>,
+    modifiers: Vec<BevyKeyCode>,
+    /// Whether the terminal has sent any releases, so they can be trusted
+    /// instead of timing keys out.
+    reports_release: bool,
+}
+
+impl HeldKeys {
+    /// Press and release modifiers to match what the terminal says is held.
+    ///
+    /// Modifier keys which were pressed on their own are already down, and
+    /// stay down until they're released as keys.
+    fn set_modifiers(&mut self, modifiers: &[BevyKeyCode], events: &mut Vec<TerminalKeyInput>) {
+        for modifier in self.modifiers.iter().rev() {
+            if !modifiers.contains(modifier) && !self.is_held(*modifier) {
+                events.push(key_input(*modifier, ButtonState::Released));
+            }
+        }
+        for modifier in modifiers {
+            if !self.modifiers.contains(modifier) && !self.is_held(*modifier) {
+                events.push(key_input(*modifier, ButtonState::Pressed));
+            }
+        }
+        self.modifiers = modifiers.to_vec();
+    }
+
+    fn is_held(&self, key: BevyKeyCode) -> bool {
+        self.keys.iter().any(|(held, _)| *held == key)
+    }
+
+    /// Press `key`, or repeat it if it's already held.
+    fn press(&mut self, key: BevyKeyCode, now: Duration, events: &mut Vec<TerminalKeyInput>) {
+        // A modifier key pressed while it's held as a modifier is already down.
+        let down = self.modifiers.contains(&key) && !self.is_held(key);
+        // Held before letting go of the others, so the modifiers stay held.
+        self.keys.retain(|(held, _)| *held != key);
+        self.keys.push((key, now));
+        if !self.reports_release {
+            let others: Vec<BevyKeyCode> = self
+                .keys
+                .iter()
+                .map(|(held, _)| *held)
+                .filter(|held| *held != key)
+                .collect();
+            for other in others {
+                if self.modifiers.contains(&other) {
+                    // Still held as a modifier, let go of along with them.
+                    self.keys.retain(|(held, _)| *held != other);
+                } else {
+                    self.release(other, events);
                 }
-                _ => (),
             }
-        } else {
-            // Timeout expired and no `Event` is available
+        }
+        if !down {
+            events.push(key_input(key, ButtonState::Pressed));
+        }
+    }
+
+    fn release(&mut self, key: BevyKeyCode, events: &mut Vec<TerminalKeyInput>) {
+        let count = self.keys.len();
+        self.keys.retain(|(held, _)| *held != key);
+        if self.keys.len() == count {
+            return;
+        }
+        events.push(key_input(key, ButtonState::Released));
+        // A modifier key let go of isn't held as a modifier either.
+        self.modifiers.retain(|modifier| *modifier != key);
+        // Modifiers only show up along with keys, so there's no telling when
+        // they're let go of on their own.
+        if self.keys.is_empty() {
+            self.set_modifiers(&[], events);
+        }
+    }
+
+    fn release_all(&mut self, events: &mut Vec<TerminalKeyInput>) {
+        let keys: Vec<BevyKeyCode> = self.keys.iter().map(|(key, _)| *key).collect();
+        for key in keys {
+            self.release(key, events);
+        }
+    }
+
+    /// Release keys which haven't been repeated within `timeout`.
+    fn release_stale(
+        &mut self,
+        now: Duration,
+        timeout: Duration,
+        events: &mut Vec<TerminalKeyInput>,
+    ) {
+        let stale: Vec<BevyKeyCode> = self
+            .keys
+            .iter()
+            .filter(|(_, last)| now.saturating_sub(*last) > timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in stale {
+            self.release(key, events);
         }
     }
 }
 
-static INPUT_THREAD_BUF: Lazy<Mutex<TerminalState>> = Lazy::new(|| {
-    Mutex::new(TerminalState {
-        handle: None,
-        key_buffer: VecDeque::default(),
-        resize: None,
+fn key_input(key_code: BevyKeyCode, state: ButtonState) -> TerminalKeyInput {
+    TerminalKeyInput::Key(KeyboardInput {
+        scan_code: 0, /* TODO, not included by vanilla termion. */
+        key_code: Some(key_code),
+        state,
     })
-});
+}
 
-#[derive(Debug, Default, Clone)]
-pub struct TerminalResize {
-    pub width: u16,
-    pub height: u16,
+/// The modifier keys held along with `event`.
+fn key_modifiers(event: &KeyEvent) -> Vec<BevyKeyCode> {
+    let shift = event.modifiers.contains(KeyModifiers::SHIFT) || is_shifted(&event.code);
+    [
+        (shift, BevyKeyCode::LShift),
+        (
+            event.modifiers.contains(KeyModifiers::CONTROL),
+            BevyKeyCode::LControl,
+        ),
+        (
+            event.modifiers.contains(KeyModifiers::ALT),
+            BevyKeyCode::LAlt,
+        ),
+        (
+            event.modifiers.contains(KeyModifiers::SUPER),
+            BevyKeyCode::LWin,
+        ),
+    ]
+    .into_iter()
+    .filter(|(held, _)| *held)
+    .map(|(_, modifier)| modifier)
+    .collect()
 }
 
-fn handle_input_buffer(
-    mut input_writer: EventWriter<KeyboardInput>,
-    mut resize_writer: EventWriter<TerminalResize>,
-) {
-    let mut input_buf = INPUT_THREAD_BUF.lock().unwrap();
+/// Everything the terminal's input is translated into.
+#[derive(SystemParam)]
+pub(super) struct InputWriters<'w> {
+    keys: EventWriter<'w, KeyboardInput>,
+    chars: EventWriter<'w, ReceivedCharacter>,
+    ordered: EventWriter<'w, TerminalKeyInput>,
+    cursor: EventWriter<'w, TerminalCursorMoved>,
+    buttons: EventWriter<'w, MouseButtonInput>,
+    wheel: EventWriter<'w, MouseWheel>,
+    terminal_buttons: EventWriter<'w, TerminalMouseButton>,
+    terminal_wheel: EventWriter<'w, TerminalMouseWheel>,
+    paste: EventWriter<'w, TerminalPaste>,
+    focus: EventWriter<'w, TerminalFocusChanged>,
+    error: EventWriter<'w, TerminalInputError>,
+    resize: EventWriter<'w, TerminalResize>,
+}
+
+impl<'w> InputWriters<'w> {
+    fn mouse_button(&mut self, button: MouseButton, state: ButtonState, position: UVec2) {
+        self.buttons.send(MouseButtonInput { button, state });
+        self.terminal_buttons.send(TerminalMouseButton {
+            button,
+            state,
+            position,
+        });
+    }
+
+    /// Scroll by `lines`, up is positive.
+    fn mouse_wheel(&mut self, lines: f32, position: UVec2) {
+        self.wheel.send(MouseWheel {
+            unit: MouseScrollUnit::Line,
+            x: 0.0,
+            y: lines,
+        });
+        self.terminal_wheel.send(TerminalMouseWheel {
+            delta: Vec2::new(0.0, lines),
+            position,
+        });
+    }
+}
 
+pub(super) fn handle_input_buffer(
+    mut terminal: ResMut<Terminal>,
+    time: Res<Time>,
+    hold_timeout: Res<KeyHoldTimeout>,
+    mut held: Local<HeldKeys>,
+    mut cursor: Local<Option<UVec2>>,
+    mut writers: InputWriters,
+) {
     let mut events = Vec::new();
-    for event in input_buf.key_buffer.drain(0..) {
-        // TODO Process.
-        //event_writer.send(KeyInputEvent { key: event.code });
-        let mut res = KeyboardInput {
-            scan_code: 0, /* TODO, not included by vanilla termion. */
-            key_code: terminal_keycode_to_bevy(&event.code),
-            state: ButtonState::Pressed,
+    let mut resize = None;
+    for event in terminal.backend_mut().poll_events() {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                log::error!("Failed to read terminal input: {}", err);
+                writers.error.send(TerminalInputError(err));
+                continue;
+            }
+        };
+        let event = match event {
+            Event::Key(event) => event,
+            Event::Paste(text) => {
+                let text = text.replace("\r\n", "\n");
+                events.extend(text.chars().map(TerminalKeyInput::Char));
+                writers.paste.send(TerminalPaste(text));
+                continue;
+            }
+            Event::FocusGained => {
+                writers.focus.send(TerminalFocusChanged(true));
+                continue;
+            }
+            Event::FocusLost => {
+                // Keys let go of while we're away won't be reported.
+                held.release_all(&mut events);
+                writers.focus.send(TerminalFocusChanged(false));
+                continue;
+            }
+            Event::Mouse(event) => {
+                let position = UVec2::new(event.column as u32, event.row as u32);
+                if *cursor != Some(position) {
+                    *cursor = Some(position);
+                    writers.cursor.send(TerminalCursorMoved { position });
+                }
+                match event.kind {
+                    MouseEventKind::Down(button) => writers.mouse_button(
+                        mouse_button_to_bevy(button),
+                        ButtonState::Pressed,
+                        position,
+                    ),
+                    MouseEventKind::Up(button) => writers.mouse_button(
+                        mouse_button_to_bevy(button),
+                        ButtonState::Released,
+                        position,
+                    ),
+                    MouseEventKind::ScrollUp => writers.mouse_wheel(1.0, position),
+                    MouseEventKind::ScrollDown => writers.mouse_wheel(-1.0, position),
+                    // Dragging is a move with the button still held.
+                    MouseEventKind::Drag(_) | MouseEventKind::Moved => (),
+                }
+                continue;
+            }
+            Event::Resize(width, height) => {
+                resize = Some(TerminalResize { width, height });
+                continue;
+            }
+        };
+        // Text goes after the key it was typed with, releasing it types nothing.
+        let text = match event.code {
+            KeyCode::Char(char)
+                if event.kind != KeyEventKind::Release
+                    && !event
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                Some(TerminalKeyInput::Char(char))
+            }
+            _ => None,
         };
-        events.push(res.clone());
-        res.state = ButtonState::Released;
-        events.push(res);
+        let Some(key_code) = terminal_keycode_to_bevy(&event.code) else {
+            log::warn!("Ignoring unknown key {:?}", event);
+            events.extend(text);
+            continue;
+        };
+        let now = time.elapsed();
+        // The terminal tells us which modifiers were held along with each
+        // key, modifier keys reported on their own are keys like any other.
+        if !matches!(event.code, KeyCode::Modifier(_)) {
+            held.set_modifiers(&key_modifiers(&event), &mut events);
+        }
+        match event.kind {
+            KeyEventKind::Press | KeyEventKind::Repeat => {
+                held.press(key_code, now, &mut events);
+            }
+            KeyEventKind::Release => {
+                held.reports_release = true;
+                held.release(key_code, &mut events);
+            }
+        }
+        events.extend(text);
+    }
+    if !held.reports_release {
+        held.release_stale(time.elapsed(), hold_timeout.0, &mut events);
+    }
+    for event in events.iter() {
+        match *event {
+            TerminalKeyInput::Key(input) => writers.keys.send(input),
+            TerminalKeyInput::Char(char) => writers.chars.send(ReceivedCharacter { char }),
+        }
     }
-    input_writer.send_batch(events);
+    writers.ordered.send_batch(events);
 
-    if let Some(resize) = input_buf.resize.take() {
-        resize_writer.send(resize);
+    if let Some(resize) = resize {
+        writers.resize.send(resize);
     }
 }
 
@@ -107,69 +451,119 @@ fn terminal_keycode_to_bevy(in_code: &crossterm::event::KeyCode) -> Option<BevyK
         KeyCode::PageUp => BevyKeyCode::PageUp,
         KeyCode::PageDown => BevyKeyCode::PageDown,
         KeyCode::Tab => BevyKeyCode::Tab,
-        KeyCode::BackTab => panic!(),
+        KeyCode::BackTab => BevyKeyCode::Tab,
         KeyCode::Delete => BevyKeyCode::Delete,
         KeyCode::Insert => BevyKeyCode::Insert,
-        KeyCode::F(u8) => todo!(),
-        KeyCode::Char(c) => charcode_to_bevy_key_code(*c),
-        KeyCode::Null => todo!(),
+        KeyCode::F(n) => function_key_to_bevy(*n)?,
+        KeyCode::Char(c) => charcode_to_bevy_key_code(*c)?,
+        KeyCode::Null => return None,
         KeyCode::Esc => BevyKeyCode::Escape,
-        KeyCode::CapsLock => todo!(),
-        KeyCode::ScrollLock => todo!(),
+        KeyCode::CapsLock => BevyKeyCode::Capital,
+        KeyCode::ScrollLock => BevyKeyCode::Scroll,
         KeyCode::NumLock => BevyKeyCode::Numlock,
-        KeyCode::PrintScreen => todo!(),
+        KeyCode::PrintScreen => BevyKeyCode::Snapshot,
         KeyCode::Pause => BevyKeyCode::Pause,
-        KeyCode::Menu => todo!(),
-        KeyCode::KeypadBegin => todo!(),
-        KeyCode::Media(media_key_codee) => todo!(),
-        KeyCode::Modifier(modifier_key_code) => todo!(),
+        KeyCode::Menu => BevyKeyCode::Apps,
+        KeyCode::KeypadBegin => BevyKeyCode::Numpad5,
+        KeyCode::Media(media) => media_key_to_bevy(*media)?,
+        KeyCode::Modifier(modifier) => modifier_key_to_bevy(*modifier)?,
+    })
+}
+
+fn mouse_button_to_bevy(button: CrosstermMouseButton) -> MouseButton {
+    match button {
+        CrosstermMouseButton::Left => MouseButton::Left,
+        CrosstermMouseButton::Right => MouseButton::Right,
+        CrosstermMouseButton::Middle => MouseButton::Middle,
+    }
+}
+
+fn function_key_to_bevy(n: u8) -> Option<BevyKeyCode> {
+    const KEYS: [BevyKeyCode; 24] = [
+        BevyKeyCode::F1,
+        BevyKeyCode::F2,
+        BevyKeyCode::F3,
+        BevyKeyCode::F4,
+        BevyKeyCode::F5,
+        BevyKeyCode::F6,
+        BevyKeyCode::F7,
+        BevyKeyCode::F8,
+        BevyKeyCode::F9,
+        BevyKeyCode::F10,
+        BevyKeyCode::F11,
+        BevyKeyCode::F12,
+        BevyKeyCode::F13,
+        BevyKeyCode::F14,
+        BevyKeyCode::F15,
+        BevyKeyCode::F16,
+        BevyKeyCode::F17,
+        BevyKeyCode::F18,
+        BevyKeyCode::F19,
+        BevyKeyCode::F20,
+        BevyKeyCode::F21,
+        BevyKeyCode::F22,
+        BevyKeyCode::F23,
+        BevyKeyCode::F24,
+    ];
+    KEYS.get((n as usize).checked_sub(1)?).copied()
+}
+
+fn media_key_to_bevy(media: MediaKeyCode) -> Option<BevyKeyCode> {
+    Some(match media {
+        MediaKeyCode::Play | MediaKeyCode::Pause | MediaKeyCode::PlayPause => {
+            BevyKeyCode::PlayPause
+        }
+        MediaKeyCode::Stop => BevyKeyCode::MediaStop,
+        MediaKeyCode::TrackNext | MediaKeyCode::FastForward => BevyKeyCode::NextTrack,
+        MediaKeyCode::TrackPrevious | MediaKeyCode::Rewind => BevyKeyCode::PrevTrack,
+        MediaKeyCode::LowerVolume => BevyKeyCode::VolumeDown,
+        MediaKeyCode::RaiseVolume => BevyKeyCode::VolumeUp,
+        MediaKeyCode::MuteVolume => BevyKeyCode::Mute,
+        MediaKeyCode::Reverse | MediaKeyCode::Record => return None,
+    })
+}
+
+fn modifier_key_to_bevy(modifier: ModifierKeyCode) -> Option<BevyKeyCode> {
+    Some(match modifier {
+        ModifierKeyCode::LeftShift => BevyKeyCode::LShift,
+        ModifierKeyCode::LeftControl => BevyKeyCode::LControl,
+        ModifierKeyCode::LeftAlt => BevyKeyCode::LAlt,
+        ModifierKeyCode::LeftSuper => BevyKeyCode::LWin,
+        ModifierKeyCode::RightShift => BevyKeyCode::RShift,
+        ModifierKeyCode::RightControl => BevyKeyCode::RControl,
+        ModifierKeyCode::RightAlt => BevyKeyCode::RAlt,
+        ModifierKeyCode::RightSuper => BevyKeyCode::RWin,
+        ModifierKeyCode::LeftHyper
+        | ModifierKeyCode::LeftMeta
+        | ModifierKeyCode::RightHyper
+        | ModifierKeyCode::RightMeta
+        | ModifierKeyCode::IsoLevel3Shift
+        | ModifierKeyCode::IsoLevel5Shift => return None,
     })
 }
 
-fn charcode_to_bevy_key_code(c: char) -> BevyKeyCode {
-    match c {
-        '1' => BevyKeyCode::Key1,
+/// Whether the key can only be typed with Shift held, on a US layout. The
+/// terminal doesn't always report Shift for these.
+fn is_shifted(code: &KeyCode) -> bool {
+    match code {
+        KeyCode::BackTab => true,
+        KeyCode::Char(c) => c.is_ascii_uppercase() || "~!#$%&(){}|\"<>?".contains(*c),
+        _ => false,
+    }
+}
+
+fn charcode_to_bevy_key_code(c: char) -> Option<BevyKeyCode> {
+    Some(match c.to_ascii_lowercase() {
+        '1' | '!' => BevyKeyCode::Key1,
         '2' => BevyKeyCode::Key2,
-        '3' => BevyKeyCode::Key3,
-        '4' => BevyKeyCode::Key4,
-        '5' => BevyKeyCode::Key5,
+        '3' | '#' => BevyKeyCode::Key3,
+        '4' | '$' => BevyKeyCode::Key4,
+        '5' | '%' => BevyKeyCode::Key5,
         '6' => BevyKeyCode::Key6,
-        '7' => BevyKeyCode::Key7,
+        '7' | '&' => BevyKeyCode::Key7,
         '8' => BevyKeyCode::Key8,
-        '9' => BevyKeyCode::Key9,
-        '0' => BevyKeyCode::Key0,
-        'A' => BevyKeyCode::A,
-        'B' => BevyKeyCode::B,
-        'C' => BevyKeyCode::C,
-        'D' => BevyKeyCode::D,
-        'E' => BevyKeyCode::E,
-        'F' => BevyKeyCode::F,
-        'G' => BevyKeyCode::G,
-        'H' => BevyKeyCode::H,
-        'I' => BevyKeyCode::I,
-        'J' => BevyKeyCode::J,
-        'K' => BevyKeyCode::K,
-        'L' => BevyKeyCode::L,
-        'M' => BevyKeyCode::M,
-        'N' => BevyKeyCode::N,
-        'O' => BevyKeyCode::O,
-        'P' => BevyKeyCode::P,
-        'Q' => BevyKeyCode::Q,
-        'R' => BevyKeyCode::R,
-        'S' => BevyKeyCode::S,
-        'T' => BevyKeyCode::T,
-        'U' => BevyKeyCode::U,
-        'V' => BevyKeyCode::V,
-        'W' => BevyKeyCode::W,
-        'X' => BevyKeyCode::X,
-        'Y' => BevyKeyCode::Y,
-        'Z' => BevyKeyCode::Z,
-        //'[' => BevKeyCode::
-        //'\\' => BevKeyCode::Backslash,
-        //']' => BevKeyCode::
-        //'^' => BevKeyCode::
-        //'_' => BevKeyCode::
-        //'`' => BevKeyCode::
+        '9' | '(' => BevyKeyCode::Key9,
+        '0' | ')' => BevyKeyCode::Key0,
         'a' => BevyKeyCode::A,
         'b' => BevyKeyCode::B,
         'c' => BevyKeyCode::C,
@@ -196,26 +590,434 @@ fn charcode_to_bevy_key_code(c: char) -> BevyKeyCode {
         'x' => BevyKeyCode::X,
         'y' => BevyKeyCode::Y,
         'z' => BevyKeyCode::Z,
-        //'{' => BevKeyCode::
-        //'|' => BevKeyCode::
-        //'}' => BevKeyCode::
-        //'~' => BevKeyCode::
-        // '\'' => BevyKeyCode::
-        _ => todo!(),
-    }
+        // Bevy has keys of their own for these, even where they share a key
+        // with something else on a US layout.
+        '@' => BevyKeyCode::At,
+        '^' => BevyKeyCode::Caret,
+        '*' => BevyKeyCode::Asterisk,
+        '+' => BevyKeyCode::Plus,
+        ':' => BevyKeyCode::Colon,
+        '_' => BevyKeyCode::Underline,
+        '[' | '{' => BevyKeyCode::LBracket,
+        ']' | '}' => BevyKeyCode::RBracket,
+        '\\' | '|' => BevyKeyCode::Backslash,
+        '`' | '~' => BevyKeyCode::Grave,
+        '\'' | '"' => BevyKeyCode::Apostrophe,
+        ',' | '<' => BevyKeyCode::Comma,
+        '.' | '>' => BevyKeyCode::Period,
+        '/' | '?' => BevyKeyCode::Slash,
+        ';' => BevyKeyCode::Semicolon,
+        '-' => BevyKeyCode::Minus,
+        '=' => BevyKeyCode::Equals,
+        ' ' => BevyKeyCode::Space,
+        '\t' => BevyKeyCode::Tab,
+        '\n' | '\r' => BevyKeyCode::Return,
+        _ => return None,
+    })
 }
 
-fn escape_listener(mut input: EventReader<KeyboardInput>, mut writer: EventWriter<AppExit>) {
-    for e in input.iter() {
-        if let Some(k) = e.key_code {
-            if [BevyKeyCode::Escape, BevyKeyCode::Q].contains(&k) {
-                writer.send(AppExit);
-            }
+/// Keep `Input<KeyCode>` up to date, so `pressed()` works while keys are held.
+fn update_key_input(mut keys: ResMut<Input<BevyKeyCode>>, mut input: EventReader<KeyboardInput>) {
+    keys.clear();
+    for event in input.iter() {
+        let Some(key) = event.key_code else {
+            continue;
+        };
+        match event.state {
+            ButtonState::Pressed => keys.press(key),
+            ButtonState::Released => keys.release(key),
         }
     }
 }
 
-fn init(onexit_register: EventWriter<RegisterOnExit>) {
-    // Spawn IO thread which reads and buffers input, we'll check every frame for input.
-    INPUT_THREAD_BUF.lock().unwrap().handle = Some(std::thread::spawn(input_thread_loop));
+/// Quit on the `quit` action.
+fn quit_listener(mut actions: EventReader<ActionEvent>, mut writer: EventWriter<AppExit>) {
+    if actions.iter().any(|event| event.action == "quit") {
+        writer.send(AppExit);
+    }
+}
+
+fn init(mut terminal: ResMut<Terminal>) {
+    terminal.backend_mut().start_input();
+}
+
+/// Let go of the terminal's input once the app is exiting.
+fn stop_input(mut terminal: ResMut<Terminal>, exit: EventReader<AppExit>) {
+    if !exit.is_empty() {
+        terminal.backend_mut().stop_input();
+    }
+}
+
+/// App translating the input of a headless terminal, without the rest of the
+/// terminal plugins.
+#[cfg(test)]
+fn input_test_app() -> (App, backend::HeadlessHandle) {
+    let backend = backend::HeadlessBackend::new(10, 4);
+    let handle = backend.handle();
+    let mut app = App::new();
+    app.insert_resource(Terminal::new(backend))
+        .add_event::<KeyboardInput>()
+        .add_event::<TerminalResize>()
+        .add_event::<ReceivedCharacter>()
+        .add_event::<TerminalKeyInput>()
+        .add_event::<MouseButtonInput>()
+        .add_event::<MouseWheel>()
+        .add_event::<TerminalMouseButton>()
+        .add_event::<TerminalMouseWheel>()
+        .add_event::<TerminalCursorMoved>()
+        .add_event::<TerminalPaste>()
+        .add_event::<TerminalFocusChanged>()
+        .add_event::<TerminalInputError>()
+        .init_resource::<Time>()
+        .init_resource::<KeyHoldTimeout>()
+        .init_resource::<Input<BevyKeyCode>>()
+        .add_system(handle_input_buffer)
+        .add_system(update_key_input.after(handle_input_buffer));
+    (app, handle)
+}
+
+/// Run a frame `millis` after startup with `events` as input, returning the
+/// keys pressed (`true`) and released in it.
+#[cfg(test)]
+fn key_frame(
+    app: &mut App,
+    handle: &backend::HeadlessHandle,
+    millis: u64,
+    events: &[KeyEvent],
+) -> Vec<(BevyKeyCode, bool)> {
+    app.world.resource_mut::<Events<KeyboardInput>>().clear();
+    let mut time = app.world.resource_mut::<Time>();
+    let now = time.startup() + Duration::from_millis(millis);
+    time.update_with_instant(now);
+    for event in events {
+        handle.push_event(Event::Key(*event));
+    }
+    app.update();
+    let events = app.world.resource::<Events<KeyboardInput>>();
+    events
+        .get_reader()
+        .iter(events)
+        .map(|event| (event.key_code.unwrap(), event.state == ButtonState::Pressed))
+        .collect()
+}
+
+/// Press and release a key in one frame.
+#[cfg(test)]
+fn tap(
+    app: &mut App,
+    handle: &backend::HeadlessHandle,
+    code: KeyCode,
+    modifiers: KeyModifiers,
+) -> Vec<(BevyKeyCode, bool)> {
+    let release = KeyEvent::new_with_kind(code, modifiers, KeyEventKind::Release);
+    key_frame(app, handle, 0, &[KeyEvent::new(code, modifiers), release])
+}
+
+#[test]
+fn test_key_translation() {
+    let (mut app, handle) = input_test_app();
+    assert_eq!(
+        tap(&mut app, &handle, KeyCode::Char('['), KeyModifiers::NONE),
+        vec![
+            (BevyKeyCode::LBracket, true),
+            (BevyKeyCode::LBracket, false)
+        ]
+    );
+    assert_eq!(
+        tap(&mut app, &handle, KeyCode::F(24), KeyModifiers::NONE),
+        vec![(BevyKeyCode::F24, true), (BevyKeyCode::F24, false)]
+    );
+    // Unknown keys are dropped rather than crashing.
+    assert_eq!(
+        tap(&mut app, &handle, KeyCode::F(25), KeyModifiers::NONE),
+        vec![]
+    );
+    assert_eq!(
+        tap(&mut app, &handle, KeyCode::Char('é'), KeyModifiers::NONE),
+        vec![]
+    );
+}
+
+#[test]
+fn test_key_modifiers() {
+    let (mut app, handle) = input_test_app();
+    assert_eq!(
+        tap(
+            &mut app,
+            &handle,
+            KeyCode::Char('s'),
+            KeyModifiers::CONTROL | KeyModifiers::ALT
+        ),
+        vec![
+            (BevyKeyCode::LControl, true),
+            (BevyKeyCode::LAlt, true),
+            (BevyKeyCode::S, true),
+            (BevyKeyCode::S, false),
+            (BevyKeyCode::LAlt, false),
+            (BevyKeyCode::LControl, false),
+        ]
+    );
+    // Shift is implied by the character even when it isn't reported.
+    assert_eq!(
+        tap(&mut app, &handle, KeyCode::Char('?'), KeyModifiers::NONE),
+        vec![
+            (BevyKeyCode::LShift, true),
+            (BevyKeyCode::Slash, true),
+            (BevyKeyCode::Slash, false),
+            (BevyKeyCode::LShift, false),
+        ]
+    );
+    // The modifier keys themselves.
+    assert_eq!(
+        tap(
+            &mut app,
+            &handle,
+            KeyCode::Modifier(ModifierKeyCode::RightShift),
+            KeyModifiers::SHIFT
+        ),
+        vec![(BevyKeyCode::RShift, true), (BevyKeyCode::RShift, false)]
+    );
+    assert_eq!(
+        tap(
+            &mut app,
+            &handle,
+            KeyCode::Modifier(ModifierKeyCode::LeftShift),
+            KeyModifiers::SHIFT
+        ),
+        vec![(BevyKeyCode::LShift, true), (BevyKeyCode::LShift, false)]
+    );
+    // Holding shift on its own and then typing with it presses it once.
+    let shift = KeyCode::Modifier(ModifierKeyCode::LeftShift);
+    let release = |code| KeyEvent::new_with_kind(code, KeyModifiers::SHIFT, KeyEventKind::Release);
+    assert_eq!(
+        key_frame(
+            &mut app,
+            &handle,
+            0,
+            &[
+                KeyEvent::new(shift, KeyModifiers::SHIFT),
+                KeyEvent::new(KeyCode::Char('?'), KeyModifiers::SHIFT),
+                release(KeyCode::Char('?')),
+                release(shift),
+            ]
+        ),
+        vec![
+            (BevyKeyCode::LShift, true),
+            (BevyKeyCode::Slash, true),
+            (BevyKeyCode::Slash, false),
+            (BevyKeyCode::LShift, false),
+        ]
+    );
+}
+
+#[test]
+fn test_text_and_paste_order() {
+    let (mut app, handle) = input_test_app();
+    let press = |code, modifiers| Event::Key(KeyEvent::new(code, modifiers));
+    let release = |code, modifiers| {
+        Event::Key(KeyEvent::new_with_kind(
+            code,
+            modifiers,
+            KeyEventKind::Release,
+        ))
+    };
+    // Unknown keys still come through as text, chords with Ctrl don't.
+    for event in [
+        press(KeyCode::Char('Ö'), KeyModifiers::SHIFT),
+        release(KeyCode::Char('Ö'), KeyModifiers::SHIFT),
+        press(KeyCode::Char('c'), KeyModifiers::CONTROL),
+        release(KeyCode::Char('c'), KeyModifiers::CONTROL),
+        Event::Paste("a\r\nb".to_string()),
+        Event::FocusLost,
+    ] {
+        handle.push_event(event);
+    }
+    app.update();
+    let events = app.world.resource::<Events<ReceivedCharacter>>();
+    let text: String = events.get_reader().iter(events).map(|c| c.char).collect();
+    assert_eq!(text, "Öa\nb");
+    let events = app.world.resource::<Events<TerminalPaste>>();
+    let pastes: Vec<_> = events.get_reader().iter(events).cloned().collect();
+    assert_eq!(pastes, vec![TerminalPaste("a\nb".to_string())]);
+    let events = app.world.resource::<Events<TerminalFocusChanged>>();
+    let focus: Vec<_> = events.get_reader().iter(events).copied().collect();
+    assert_eq!(focus, vec![TerminalFocusChanged(false)]);
+
+    // Text comes right after the key it was typed with.
+    let mut reader = app
+        .world
+        .resource::<Events<TerminalKeyInput>>()
+        .get_reader();
+    reader.clear(app.world.resource::<Events<TerminalKeyInput>>());
+    for event in [
+        press(KeyCode::Char('a'), KeyModifiers::NONE),
+        release(KeyCode::Char('a'), KeyModifiers::NONE),
+        press(KeyCode::Backspace, KeyModifiers::NONE),
+        release(KeyCode::Backspace, KeyModifiers::NONE),
+    ] {
+        handle.push_event(event);
+    }
+    app.update();
+    let events = app.world.resource::<Events<TerminalKeyInput>>();
+    assert_eq!(
+        reader.iter(events).copied().collect::<Vec<_>>(),
+        vec![
+            key_input(BevyKeyCode::A, ButtonState::Pressed),
+            TerminalKeyInput::Char('a'),
+            key_input(BevyKeyCode::A, ButtonState::Released),
+            key_input(BevyKeyCode::Back, ButtonState::Pressed),
+            key_input(BevyKeyCode::Back, ButtonState::Released),
+        ]
+    );
+}
+
+#[test]
+fn test_mouse_cells() {
+    let (mut app, handle) = input_test_app();
+    let mouse = |kind, column, row| {
+        Event::Mouse(crossterm::event::MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        })
+    };
+    for event in [
+        mouse(MouseEventKind::Down(CrosstermMouseButton::Left), 3, 2),
+        mouse(MouseEventKind::Drag(CrosstermMouseButton::Left), 5, 2),
+        mouse(MouseEventKind::Up(CrosstermMouseButton::Left), 5, 2),
+        mouse(MouseEventKind::ScrollDown, 5, 2),
+    ] {
+        handle.push_event(event);
+    }
+    app.update();
+    let events = app.world.resource::<Events<TerminalCursorMoved>>();
+    let moves: Vec<UVec2> = events
+        .get_reader()
+        .iter(events)
+        .map(|e| e.position)
+        .collect();
+    assert_eq!(moves, vec![UVec2::new(3, 2), UVec2::new(5, 2)]);
+    let events = app.world.resource::<Events<MouseButtonInput>>();
+    let buttons: Vec<_> = events
+        .get_reader()
+        .iter(events)
+        .map(|e| (e.button, e.state == ButtonState::Pressed))
+        .collect();
+    assert_eq!(
+        buttons,
+        vec![(MouseButton::Left, true), (MouseButton::Left, false)]
+    );
+    let events = app.world.resource::<Events<MouseWheel>>();
+    assert_eq!(events.get_reader().iter(events).next().unwrap().y, -1.0);
+    let events = app.world.resource::<Events<TerminalMouseWheel>>();
+    assert_eq!(
+        events
+            .get_reader()
+            .iter(events)
+            .copied()
+            .collect::<Vec<_>>(),
+        vec![TerminalMouseWheel {
+            delta: Vec2::new(0.0, -1.0),
+            position: UVec2::new(5, 2),
+        }]
+    );
+
+    // Two clicks in one frame each keep the cell they were in.
+    for event in [
+        mouse(MouseEventKind::Down(CrosstermMouseButton::Left), 1, 0),
+        mouse(MouseEventKind::Up(CrosstermMouseButton::Left), 1, 0),
+        mouse(MouseEventKind::Down(CrosstermMouseButton::Right), 8, 3),
+        mouse(MouseEventKind::Up(CrosstermMouseButton::Right), 8, 3),
+    ] {
+        handle.push_event(event);
+    }
+    let mut reader = app
+        .world
+        .resource::<Events<TerminalMouseButton>>()
+        .get_reader();
+    reader.clear(app.world.resource::<Events<TerminalMouseButton>>());
+    app.update();
+    let events = app.world.resource::<Events<TerminalMouseButton>>();
+    let clicks: Vec<_> = reader
+        .iter(events)
+        .filter(|e| e.state == ButtonState::Pressed)
+        .map(|e| (e.button, e.position))
+        .collect();
+    assert_eq!(
+        clicks,
+        vec![
+            (MouseButton::Left, UVec2::new(1, 0)),
+            (MouseButton::Right, UVec2::new(8, 3)),
+        ]
+    );
+}
+
+#[test]
+fn test_key_hold_timeout() {
+    let (mut app, handle) = input_test_app();
+    app.insert_resource(KeyHoldTimeout(Duration::from_millis(500)));
+    let press = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+    // Until the terminal reports releases keys are held until they stop
+    // repeating, or another key is pressed.
+    let w = KeyCode::Char('w');
+    assert_eq!(
+        key_frame(&mut app, &handle, 0, &[press(w)]),
+        vec![(BevyKeyCode::W, true)]
+    );
+    assert_eq!(
+        key_frame(&mut app, &handle, 300, &[press(w)]),
+        vec![(BevyKeyCode::W, true)]
+    );
+    assert!(app
+        .world
+        .resource::<Input<BevyKeyCode>>()
+        .pressed(BevyKeyCode::W));
+    assert_eq!(key_frame(&mut app, &handle, 700, &[]), vec![]);
+    assert_eq!(
+        key_frame(&mut app, &handle, 900, &[press(KeyCode::Char('d'))]),
+        vec![(BevyKeyCode::W, false), (BevyKeyCode::D, true)]
+    );
+    assert_eq!(
+        key_frame(&mut app, &handle, 1500, &[]),
+        vec![(BevyKeyCode::D, false)]
+    );
+    let keys = app.world.resource::<Input<BevyKeyCode>>();
+    assert!(!keys.pressed(BevyKeyCode::D) && keys.just_released(BevyKeyCode::D));
+    // Letting go of the last key doesn't let go of the modifiers of the next.
+    key_frame(&mut app, &handle, 1600, &[press(w)]);
+    assert_eq!(
+        key_frame(&mut app, &handle, 1700, &[press(KeyCode::Char('?'))]),
+        vec![
+            (BevyKeyCode::LShift, true),
+            (BevyKeyCode::W, false),
+            (BevyKeyCode::Slash, true)
+        ]
+    );
+
+    // Once a release is seen they're trusted, and keys stay held.
+    let release = KeyEvent::new_with_kind(w, KeyModifiers::NONE, KeyEventKind::Release);
+    key_frame(&mut app, &handle, 2000, &[press(w), release]);
+    key_frame(&mut app, &handle, 2100, &[press(w)]);
+    assert_eq!(key_frame(&mut app, &handle, 5000, &[]), vec![]);
+    assert!(app
+        .world
+        .resource::<Input<BevyKeyCode>>()
+        .pressed(BevyKeyCode::W));
+}
+
+#[test]
+fn test_input_error() {
+    let (mut app, handle) = input_test_app();
+    // Losing the terminal is reported rather than input just going quiet.
+    handle.fail_input(std::io::ErrorKind::BrokenPipe.into());
+    app.update();
+    let events = app.world.resource::<Events<TerminalInputError>>();
+    let errors: Vec<_> = events
+        .get_reader()
+        .iter(events)
+        .map(|e| e.0.kind())
+        .collect();
+    assert_eq!(errors, vec![std::io::ErrorKind::BrokenPipe]);
 }
@@ -1,9 +1,20 @@
+pub mod action;
+pub mod animation;
+pub mod backend;
 pub mod camera;
+pub mod focus;
 pub mod input;
 pub mod render;
+pub mod screen;
+pub mod spatial;
+pub mod sprite;
+pub mod text;
+pub mod widget;
 
 mod display;
 
+pub use self::display::{Cell, CellStyle};
+
 use crate::prelude::*;
 
 #[derive(Default)]
@@ -12,8 +23,52 @@ pub struct TerminalPlugin {}
 impl Plugin for TerminalPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(self::input::TerminalInputPlugin::default())
+            .add_plugin(self::focus::TerminalFocusPlugin::default())
+            .add_plugin(self::action::TerminalActionPlugin::default())
             .add_plugin(self::display::TerminalDisplayPlugin::default())
             .add_plugin(self::render::TerminalRenderPlugin::default())
+            .add_plugin(self::animation::TerminalAnimationPlugin::default())
+            .add_plugin(self::widget::TerminalWidgetPlugin::default())
             .add_plugin(self::camera::TerminalCamera2dPlugin::default());
     }
 }
+
+#[test]
+fn test_headless_frame() {
+    use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+    use crossterm::style::{Attribute, Color};
+
+    let backend = backend::HeadlessBackend::new(10, 4);
+    let handle = backend.handle();
+    let mut app = App::new();
+    app.insert_resource(backend::Terminal::new(backend))
+        .add_plugin(TerminalPlugin::default());
+    app.world.spawn(render::TextureRect {
+        texture: 'a',
+        style: CellStyle::new(Color::Yellow, Color::Blue).with_attribute(Attribute::Bold),
+        dim: Vec2::new(2.0, 1.0),
+        loc: Vec2::new(0.0, 0.0),
+        loc_z: 1.0,
+    });
+    app.update();
+    app.update();
+    assert_eq!(
+        handle.rows(),
+        vec!["          ", "    aa    ", "          ", "          "]
+    );
+    assert_eq!(
+        handle.cell(4, 1).unwrap().style,
+        CellStyle::new(Color::Yellow, Color::Blue).with_attribute(Attribute::Bold)
+    );
+    assert_eq!(handle.cell(3, 1).unwrap().style, CellStyle::default());
+
+    handle.push_event(Event::Key(KeyEvent::new(
+        KeyCode::Char('q'),
+        KeyModifiers::NONE,
+    )));
+    app.update();
+    assert!(!app
+        .world
+        .resource::<Events<bevy::app::AppExit>>()
+        .is_empty());
+}
@@ -4,45 +4,175 @@ use std::{
 };
 
 use bevy::math::Vec3Swizzles;
+use bevy::utils::HashMap;
+use crossterm::style::Attribute;
 
 /// This plugin is responsible for providing Components which can be rendered down onto a terminal screen and then painted.
-/// Render logic is super simple: The TextureRect with the highest z value will be painted.
+/// Render logic is super simple: The TextureRect with the highest z value will be painted, ties are broken by
+/// entity id. Rects in [`ScreenSpace`] are drawn over everything in the world. Rects with a [`ZLevel`]
+/// only show on the camera's level, or dimmed when the camera looks down on them.
 use crate::prelude::*;
 
 use super::{
-    camera::TerminalCamera2d,
-    display::{self, TerminalDisplayBuffer},
+    camera::{
+        self, CameraProjection, GlyphAggregation, RenderLayers, TerminalCamera2d, ZLevel, Zoom,
+        ZoomPriority,
+    },
+    display::{self, Cell, CellStyle, TerminalDisplayBuffer, VirtualDisplayBuffer},
+    screen::{self, ScreenSpace},
+    spatial::{self, SpatialIndex},
+    sprite::Sprite,
+    text,
 };
 
 #[derive(Component, Clone)]
 pub struct TextureRect {
     pub texture: char,
+    pub style: CellStyle,
     pub dim: Vec2,
     pub loc: Vec2,
     pub loc_z: f32,
 }
 
+impl TextureRect {
+    /// Texture which draws nothing, whatever is below shows through.
+    pub const TRANSPARENT: char = '\0';
+
+    /// The cell drawn at `local` within the rect, `None` if it's transparent.
+    fn fragment(&self, sprite: Option<&Sprite>, local: UVec2) -> Option<Cell> {
+        match sprite {
+            Some(sprite) => sprite.sample(local, self.dim.ceil().as_uvec2()),
+            None => {
+                (self.texture != Self::TRANSPARENT).then(|| Cell::new(self.texture, self.style))
+            }
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct TerminalRenderPlugin();
 
 impl Plugin for TerminalRenderPlugin {
     fn build(&self, app: &mut App) {
-        app.add_system(render);
+        app.init_resource::<SpatialIndex>()
+            .add_system(
+                screen::layout_screen_space
+                    .after(display::handle_terminal_resize)
+                    .before(text::layout_text)
+                    .before(spatial::update_spatial_index),
+            )
+            // Text needs its sprite in place before it can be drawn.
+            .add_system(text::layout_text)
+            .add_system(apply_system_buffers.after(text::layout_text).before(render))
+            .add_system(spatial::update_spatial_index.before(render))
+            .add_system(
+                render
+                    .after(display::handle_terminal_resize)
+                    .before(display::paint),
+            );
     }
 }
 
 /// Local cache for the rendering function. Rather than needing to allocate a new Vec, each time keep one static.
 #[derive(Default)]
-struct RenderCache {
+pub(super) struct RenderCache {
+    /// Depth buffer, one tile for each cell of the display buffer.
     buf: Vec<Tile>,
-    sort_cache: Vec<TextureRect>,
-    width: u16,
-    depth: u16,
+    /// Which view each cell of the display buffer belongs to, the one whose
+    /// viewport is on top there.
+    owners: Vec<usize>,
+    visible: Vec<Entity>,
+    draw_list: Vec<(Entity, usize, CellRect)>,
+    /// Where each entity was drawn last render and by which view, used to
+    /// find what needs to be redrawn when they change.
+    bounds: HashMap<Entity, Vec<(usize, CellRect)>>,
+    /// Size of the display buffer we last rendered to.
+    size: (u16, u16),
+    /// The world drawn a tile per cell for zoomed out views, kept between
+    /// renders so it's only allocated again when it grows.
+    off_screen: Option<Box<(RenderCache, VirtualDisplayBuffer)>>,
 }
 
+/// Depth of whatever was drawn into a cell.
+#[derive(Clone, Copy)]
 struct Tile {
-    texture: char,
+    /// Screen-space rects are above the world no matter their z.
+    screen_space: bool,
+    /// Levels below the camera's, anything on a lower level is below
+    /// everything on a higher one.
+    below: u8,
     z_depth: f32,
+    entity: Option<Entity>,
+    /// The view which drew it, only the owner of a cell can draw world rects
+    /// into it.
+    view: usize,
+}
+
+impl Default for Tile {
+    fn default() -> Self {
+        Self {
+            screen_space: false,
+            below: u8::MAX,
+            z_depth: f32::NEG_INFINITY,
+            entity: None,
+            view: NO_VIEW,
+        }
+    }
+}
+
+/// Owner of the cells no camera draws to.
+const NO_VIEW: usize = usize::MAX;
+
+impl Tile {
+    /// Whether this tile is drawn over `other`. Equal depths are broken by
+    /// entity so the result doesn't depend on draw order.
+    fn is_above(&self, other: &Tile) -> bool {
+        let key = |tile: &Tile| tile.entity.map(|e| (e.index(), e.generation()));
+        let depth = |tile: &Tile| (tile.screen_space, -(tile.below as i32), tile.z_depth);
+        match depth(self).partial_cmp(&depth(other)) {
+            Some(Ordering::Greater) => true,
+            Some(Ordering::Equal) => key(self) > key(other),
+            _ => false,
+        }
+    }
+}
+
+impl RenderCache {
+    fn tile_mut(&mut self, col: u32, row: u32) -> &mut Tile {
+        &mut self.buf[col as usize + row as usize * self.size.0 as usize]
+    }
+
+    fn owner(&self, col: u32, row: u32) -> usize {
+        self.owners[col as usize + row as usize * self.size.0 as usize]
+    }
+
+    /// Hand the cells of `region` to `view`, over whoever had them.
+    fn claim(&mut self, view: usize, region: CellRect) {
+        for row in region.min.y..region.max.y {
+            for col in region.min.x..region.max.x {
+                self.owners[col as usize + row as usize * self.size.0 as usize] = view;
+            }
+        }
+    }
+
+    /// Forget where `entity` was drawn, returning the regions to redraw.
+    fn forget(&mut self, entity: Entity) -> impl Iterator<Item = CellRect> {
+        self.bounds
+            .remove(&entity)
+            .into_iter()
+            .flatten()
+            .map(|(_, bounds)| bounds)
+    }
+
+    /// Blank out a region of both the depth buffer and display buffer.
+    fn clear(&mut self, display_buf: &mut VirtualDisplayBuffer, region: CellRect) {
+        for row in region.min.y..region.max.y {
+            for col in region.min.x..region.max.x {
+                *self.tile_mut(col, row) = Tile::default();
+                display_buf.set(col as u16, row as u16, Cell::default());
+            }
+        }
+    }
 }
 
 fn advance_by<T>(mut itr: impl Iterator<Item = T>, n: usize) -> Result<(), usize> {
@@ -52,158 +182,1034 @@ fn advance_by<T>(mut itr: impl Iterator<Item = T>, n: usize) -> Result<(), usize
     Ok(())
 }
 
-// To normalize x:
-// div_x = max_value_x - min_value_x
-// f(x) = (x - min_value_x) / div_x
-#[inline]
-fn normalize_point(point: Vec2, max_point: Vec2, min_point: Vec2) -> Vec2 {
-    let div = max_point - min_point;
-    (point - min_point) / div
+/// Rectangle of terminal cells, `max` is exclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CellRect {
+    pub min: UVec2,
+    pub max: UVec2,
 }
 
-#[inline]
-fn normalized_point_to_tile(point: Vec2, width: u16, height: u16) -> (u16, u16) {
-    (
-        (point.x * width as f32) as u16,
-        (point.y * height as f32) as u16,
-    )
+impl CellRect {
+    pub fn new(min_x: u32, min_y: u32, max_x: u32, max_y: u32) -> Self {
+        Self {
+            min: UVec2::new(min_x, min_y),
+            max: UVec2::new(max_x, max_y),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x >= self.max.x || self.min.y >= self.max.y
+    }
+
+    /// Columns and rows covered.
+    pub fn size(&self) -> UVec2 {
+        self.max.max(self.min) - self.min
+    }
+
+    pub fn contains(&self, cell: UVec2) -> bool {
+        cell.cmpge(self.min).all() && cell.cmplt(self.max).all()
+    }
+
+    pub fn intersect(&self, other: CellRect) -> CellRect {
+        CellRect {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        }
+    }
+
+    pub fn overlaps(&self, other: CellRect) -> bool {
+        !self.intersect(other).is_empty()
+    }
+
+    pub fn area(&self) -> u32 {
+        if self.is_empty() {
+            return 0;
+        }
+        (self.max.x - self.min.x) * (self.max.y - self.min.y)
+    }
+
+    /// Grow the rect by `cols` on the left and right.
+    fn pad_x(&self, cols: u32) -> CellRect {
+        CellRect {
+            min: UVec2::new(self.min.x.saturating_sub(cols), self.min.y),
+            max: UVec2::new(self.max.x + cols, self.max.y),
+        }
+    }
+}
+
+/// Projection of world space onto the display buffer for a single frame,
+/// one for each camera plus one for screen space.
+#[derive(Clone, Copy)]
+struct View {
+    projection: CameraProjection,
+    /// Tiles along each side of the block summarized by each cell, 1 unless
+    /// zoomed out.
+    block: u32,
+    aggregation: GlyphAggregation,
+    level: ZLevel,
+    /// Levels below `level` shown through open air.
+    look_down: u8,
+    /// Which rects are drawn, `None` for the screen-space overlay which draws
+    /// only rects in [`ScreenSpace`].
+    layers: Option<RenderLayers>,
+    width: u16,
+    height: u16,
+}
+
+impl View {
+    fn new(camera: &TerminalCamera2d, screen: UVec2) -> Self {
+        // Get bounds/dimensions to paint, we won't need to pain anything outside bounds.
+        let projection = camera.projection(screen);
+        let size = projection.viewport.size();
+        let block = match camera.zoom() {
+            Zoom::Out(n) => n.max(1) as u32,
+            _ => 1,
+        };
+        Self {
+            projection,
+            block,
+            aggregation: camera.settings_ref().aggregation(),
+            level: camera.level(),
+            look_down: camera.settings_ref().look_down(),
+            layers: Some(camera.layers()),
+            width: size.x as u16,
+            height: size.y as u16,
+        }
+    }
+
+    /// Draws screen-space rects over the whole display buffer.
+    fn overlay(width: u16, height: u16) -> Self {
+        Self {
+            projection: CameraProjection {
+                world: Rect::default(),
+                cell_size: Vec2::ONE,
+                viewport: CellRect::new(0, 0, width as u32, height as u32),
+            },
+            block: 1,
+            aggregation: GlyphAggregation::default(),
+            level: ZLevel::default(),
+            look_down: 0,
+            layers: None,
+            width,
+            height,
+        }
+    }
+
+    /// The same view with a cell for every tile of each zoomed out block,
+    /// drawn to a buffer of its own.
+    fn unzoomed(&self) -> View {
+        let block = self.block as u16;
+        let (width, height) = (
+            self.width.saturating_mul(block),
+            self.height.saturating_mul(block),
+        );
+        View {
+            projection: CameraProjection {
+                cell_size: self.projection.cell_size / self.block as f32,
+                viewport: CellRect::new(0, 0, width as u32, height as u32),
+                ..self.projection
+            },
+            block: 1,
+            width,
+            height,
+            ..*self
+        }
+    }
+
+    fn screen(&self) -> CellRect {
+        self.projection.viewport
+    }
+
+    fn world(&self) -> Rect {
+        self.projection.world
+    }
+
+    /// Warn when the camera sees more than fits in its viewport.
+    fn warn_if_clipped(&self) {
+        let cells_in_view = self.projection.world.size() / self.projection.cell_size;
+        if cells_in_view.x as u16 > self.width || cells_in_view.y as u16 > self.height {
+            log::warn!(
+                "Camera dimmensions larger than its viewport ({:?}) > {:?}",
+                (cells_in_view.x as usize, cells_in_view.y as usize),
+                (self.width, self.height)
+            );
+        }
+    }
+
+    /// Whether this view draws the rect at all.
+    fn shows(&self, (_, _, screen_space, layers, level): TextureItem) -> bool {
+        match self.layers {
+            Some(view_layers) => {
+                screen_space.is_none()
+                    && view_layers.intersects(layers.unwrap_or(&RenderLayers::default()))
+                    && self.levels_below(level).is_some()
+            }
+            None => screen_space.is_some(),
+        }
+    }
+
+    /// How far below the view's level a rect on `level` is, `None` if it's
+    /// above or too far below to be seen.
+    fn levels_below(&self, level: Option<&ZLevel>) -> Option<u8> {
+        camera::levels_below(self.level, self.look_down, level)
+    }
+
+    /// The cells of the display buffer `texture` covers, if it's on screen at all.
+    fn screen_bounds(&self, texture: &TextureRect, screen_space: bool) -> Option<CellRect> {
+        let rect = Rect::from_center_size(texture.loc, texture.dim);
+        if screen_space {
+            let min = rect.min.max(Vec2::ZERO).floor().as_uvec2();
+            let max = rect.max.ceil().max(Vec2::ZERO).as_uvec2();
+            let bounds = CellRect { min, max }.intersect(self.screen());
+            return (!bounds.is_empty()).then_some(bounds);
+        }
+        if self.block > 1 {
+            // Zoomed out the rect can be smaller than a cell, it covers every
+            // cell whose block it has a tile in.
+            let tiles = self.unzoomed().screen_bounds(texture, false)?;
+            let block = UVec2::splat(self.block);
+            let min = self.screen().min;
+            return Some(CellRect {
+                min: min + tiles.min / block,
+                max: min + (tiles.max + block - UVec2::ONE) / block,
+            });
+        }
+        self.projection.world_rect_to_cells(rect)
+    }
+
+    /// Which cell of `texture` is shown at the given screen cell.
+    fn local_cell(&self, texture: &TextureRect, screen_space: bool, col: u32, row: u32) -> UVec2 {
+        let rect_min = texture.loc - texture.dim / 2.0;
+        if screen_space {
+            return (Vec2::new(col as f32, row as f32) - rect_min.floor())
+                .max(Vec2::ZERO)
+                .as_uvec2();
+        }
+        // Sample the world at the center of the cell.
+        let world = self.projection.cell_to_world(UVec2::new(col, row));
+        (world - rect_min).max(Vec2::ZERO).floor().as_uvec2()
+    }
 }
 
-fn render(
+/// Anything which changes what a rect looks like.
+type TextureChanged = Or<(
+    Changed<TextureRect>,
+    Changed<Sprite>,
+    Changed<ScreenSpace>,
+    Changed<RenderLayers>,
+    Changed<ZLevel>,
+)>;
+
+/// Everything needed to draw a rect.
+type TextureQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static TextureRect,
+        Option<&'static Sprite>,
+        Option<&'static ScreenSpace>,
+        Option<&'static RenderLayers>,
+        Option<&'static ZLevel>,
+    ),
+>;
+
+type TextureItem<'a> = (
+    &'a TextureRect,
+    Option<&'a Sprite>,
+    Option<&'a ScreenSpace>,
+    Option<&'a RenderLayers>,
+    Option<&'a ZLevel>,
+);
+
+type CameraQuery<'w, 's> = Query<'w, 's, (Entity, Ref<'static, TerminalCamera2d>)>;
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn render(
     mut cache: Local<RenderCache>,
-    changed: Query<&TextureRect, Changed<TextureRect>>,
-    query: Query<&TextureRect>,
-    camera: ResMut<TerminalCamera2d>,
+    changed: Query<Entity, TextureChanged>,
+    mut removed: RemovedComponents<TextureRect>,
+    mut removed_sprites: RemovedComponents<Sprite>,
+    mut removed_screen_space: RemovedComponents<ScreenSpace>,
+    mut removed_layers: RemovedComponents<RenderLayers>,
+    mut removed_levels: RemovedComponents<ZLevel>,
+    mut removed_cameras: RemovedComponents<TerminalCamera2d>,
+    query: TextureQuery,
+    screen_space: Query<Entity, (With<ScreenSpace>, With<TextureRect>)>,
+    priorities: Query<&ZoomPriority>,
+    index: Res<SpatialIndex>,
+    cameras: CameraQuery,
     mut display_buf: ResMut<TerminalDisplayBuffer>,
 ) {
-    if changed.is_empty() && !display_buf.is_changed() && !camera.is_changed() {
-        return;
-    }
     let buf_width = display_buf.0.width;
     let buf_height = display_buf.0.height;
-    if camera.settings_ref().autoresize() {
-        camera.dim().x = buf_width as f32;
-        camera.dim().y = buf_height as f32;
+    // Anything which moves the whole view needs a full render, otherwise we
+    // only need to redraw where rects changed.
+    let full = cameras.iter().any(|(_, camera)| camera.is_changed())
+        || !removed_cameras.is_empty()
+        || display_buf.get_flush()
+        || cache.size != (buf_width, buf_height);
+    if !full
+        && changed.is_empty()
+        && removed.is_empty()
+        && removed_sprites.is_empty()
+        && removed_screen_space.is_empty()
+        && removed_layers.is_empty()
+        && removed_levels.is_empty()
+    {
+        return;
+    }
+    let views = views(&cameras, buf_width, buf_height);
+    let display_buf = &mut display_buf.0;
+
+    if full {
+        render_full(
+            &mut cache,
+            &views,
+            &index,
+            &query,
+            &screen_space,
+            &priorities,
+            display_buf,
+        );
+        removed.clear();
+        removed_sprites.clear();
+        removed_screen_space.clear();
+        removed_layers.clear();
+        removed_levels.clear();
+        removed_cameras.clear();
+        return;
+    }
+
+    let mut damage = Vec::new();
+    for entity in removed.iter() {
+        damage.extend(cache.forget(entity));
+    }
+    // Losing a sprite redraws the rect with its plain texture, losing screen
+    // space puts it back in the world.
+    for entity in changed
+        .iter()
+        .chain(removed_sprites.iter())
+        .chain(removed_screen_space.iter())
+        .chain(removed_layers.iter())
+        .chain(removed_levels.iter())
+    {
+        damage.extend(cache.forget(entity));
+        let Ok(item) = query.get(entity) else {
+            continue;
+        };
+        for (i, view) in views.iter().enumerate() {
+            if !view.shows(item) {
+                continue;
+            }
+            if let Some(bounds) = view.screen_bounds(item.0, item.2.is_some()) {
+                cache.bounds.entry(entity).or_default().push((i, bounds));
+                damage.push(bounds);
+            }
+        }
+    }
+    if damage.is_empty() {
+        return;
+    }
+    // Wide glyphs straddling the edge of a damaged region get broken up when
+    // it's cleared, include the neighbouring columns so they're redrawn.
+    let screen = CellRect::new(0, 0, buf_width as u32, buf_height as u32);
+    for region in damage.iter_mut() {
+        *region = region.pad_x(1).intersect(screen);
+    }
+    // Every cell of a zoomed out view depends on a whole block of the world,
+    // so any damage to one redraws its whole viewport. That damages whatever
+    // it overlaps in turn.
+    let mut aggregated = Vec::new();
+    while let Some((i, view)) = views.iter().enumerate().find(|(i, view)| {
+        view.block > 1
+            && !aggregated.contains(i)
+            && damage.iter().any(|region| region.overlaps(view.screen()))
+    }) {
+        aggregated.push(i);
+        damage.push(view.screen().pad_x(1).intersect(screen));
+    }
+    // Once enough of the screen is damaged it's cheaper to just redraw it all.
+    if damage.iter().map(CellRect::area).sum::<u32>() >= screen.area() / 2 {
+        render_full(
+            &mut cache,
+            &views,
+            &index,
+            &query,
+            &screen_space,
+            &priorities,
+            display_buf,
+        );
+        return;
     }
 
-    // Get bounds/dimensions to paint, we won't need to pain anything outside bounds.
-    let camera_rec = Rect::from_center_size(camera.loc().xy(), camera.dim());
+    for region in damage.iter() {
+        cache.clear(display_buf, *region);
+    }
+    for i in aggregated {
+        render_aggregated(
+            &mut cache,
+            (i, &views[i]),
+            &index,
+            &query,
+            &priorities,
+            display_buf,
+        );
+    }
 
-    cache.sort_cache.clear();
-    cache
-        .sort_cache
-        .extend(query.iter().map(|rect_ref| (rect_ref).clone()));
-    cache
-        .sort_cache
-        .sort_by(|l, r| r.loc_z.partial_cmp(&l.loc_z).unwrap());
+    // Only the rects overlapping the damage need to be redrawn.
+    let RenderCache {
+        bounds, draw_list, ..
+    } = &mut *cache;
+    draw_list.clear();
+    for (entity, drawn) in bounds.iter() {
+        draw_list.extend(
+            drawn
+                .iter()
+                .filter(|(view, bounds)| {
+                    views[*view].block == 1 && damage.iter().any(|region| region.overlaps(*bounds))
+                })
+                .map(|(view, bounds)| (*entity, *view, *bounds)),
+        );
+    }
+    for (entity, view, bounds) in std::mem::take(&mut cache.draw_list) {
+        let texture = query.get(entity).unwrap();
+        for region in damage.iter() {
+            rasterize(
+                &mut cache,
+                display_buf,
+                (view, &views[view]),
+                entity,
+                texture,
+                bounds,
+                *region,
+            );
+        }
+    }
+}
 
+/// A view for each camera in the order they're drawn, lowest order first,
+/// then the screen-space overlay.
+fn views(cameras: &CameraQuery, width: u16, height: u16) -> Vec<View> {
+    let mut sorted: Vec<_> = cameras.iter().collect();
+    sorted.sort_by_key(|(entity, camera)| (camera.order(), *entity));
+    let screen = UVec2::new(width as u32, height as u32);
+    sorted
+        .into_iter()
+        .map(|(_, camera)| View::new(&camera, screen))
+        .chain(std::iter::once(View::overlay(width, height)))
+        .collect()
+}
+
+/// Clear the display buffer and draw every visible rect.
+fn render_full(
+    cache: &mut RenderCache,
+    views: &[View],
+    index: &SpatialIndex,
+    query: &TextureQuery,
+    screen_space: &Query<Entity, (With<ScreenSpace>, With<TextureRect>)>,
+    priorities: &Query<&ZoomPriority>,
+    display_buf: &mut VirtualDisplayBuffer,
+) {
     // Start by clearing the frame buffer, render will completely fill it.
-    display_buf.0.buf.clear();
-    display_buf
-        .0
-        .buf
-        .resize((buf_height * buf_width) as usize, ' ');
-
-    if buf_width < camera.dim().x as u16 || buf_height < camera.dim().y as u16 {
-        log::warn!(
-            "Camera dimmensions larger than terminal ({:?}) > {:?}",
-            (camera.dim().x as usize, camera.dim().y as usize),
-            (buf_width, buf_height)
+    let (width, height) = (display_buf.width, display_buf.height);
+    reset(cache, display_buf, width, height);
+    cache.bounds.clear();
+    cache.draw_list.clear();
+    // Later cameras cover earlier ones, the overlay doesn't need any cells
+    // of its own.
+    for (i, view) in views.iter().enumerate() {
+        if view.layers.is_some() {
+            cache.claim(i, view.screen());
+            view.warn_if_clipped();
+        }
+    }
+
+    for (i, view) in views.iter().enumerate() {
+        // Only look at the rects which are actually in view.
+        let RenderCache {
+            visible,
+            bounds,
+            draw_list,
+            ..
+        } = &mut *cache;
+        visible.clear();
+        match view.layers {
+            Some(_) => index.query(view.world(), visible),
+            None => visible.extend(screen_space.iter()),
+        }
+        for entity in visible.iter() {
+            let Ok(item) = query.get(*entity) else {
+                continue;
+            };
+            if !view.shows(item) {
+                continue;
+            }
+            if let Some(texture_bounds) = view.screen_bounds(item.0, item.2.is_some()) {
+                bounds.entry(*entity).or_default().push((i, texture_bounds));
+                // Zoomed out views are drawn a block at a time instead, the
+                // bounds are only kept to know when to redraw them.
+                if view.block == 1 {
+                    draw_list.push((*entity, i, texture_bounds));
+                }
+            }
+        }
+        if view.block > 1 {
+            render_aggregated(cache, (i, view), index, query, priorities, display_buf);
+        }
+    }
+
+    // For each tile keep the texture of the max z, the depth buffer means
+    // draw order doesn't matter. The one exception is the leftover half of a
+    // wide glyph split by something above it: it's blanked, and only what's
+    // drawn there afterwards shows.
+    for (entity, view, bounds) in std::mem::take(&mut cache.draw_list) {
+        rasterize(
+            cache,
+            display_buf,
+            (view, &views[view]),
+            entity,
+            query.get(entity).unwrap(),
+            bounds,
+            views[view].screen(),
         );
     }
+}
+
+/// Blank both the depth buffer and display buffer, ready for a full render.
+fn reset(cache: &mut RenderCache, display_buf: &mut VirtualDisplayBuffer, width: u16, height: u16) {
+    cache.size = (width, height);
+    let cells = height as usize * width as usize;
+    display_buf.buf.clear();
+    display_buf.buf.resize(cells, Cell::default());
+    cache.buf.clear();
+    cache.buf.resize(cells, Tile::default());
+    cache.owners.clear();
+    cache.owners.resize(cells, NO_VIEW);
+}
 
-    // For each tile keep the texture of the max z.
-    // (Obviously this is the naive and super inefficient way to do this, but I don't know anything about SIMD/GPU optimizations for layering textures...)
-    for texture in cache.sort_cache.iter() {
-        // Iterate through all textures,
-        let overlap = camera_rec.intersect(Rect::from_center_size(texture.loc, texture.dim));
-        if overlap.is_empty() {
+/// Draw the world zoomed out, each cell showing one of the tiles in the
+/// block under it as picked by the view's aggregation.
+fn render_aggregated(
+    cache: &mut RenderCache,
+    (i, view): (usize, &View),
+    index: &SpatialIndex,
+    query: &TextureQuery,
+    priorities: &Query<&ZoomPriority>,
+    display_buf: &mut VirtualDisplayBuffer,
+) {
+    // Draw the world a tile per cell off screen first.
+    let tiles = view.unzoomed();
+    let mut off_screen = cache.off_screen.take().unwrap_or_else(|| {
+        Box::new((
+            RenderCache::default(),
+            VirtualDisplayBuffer {
+                buf: Vec::new(),
+                width: 0,
+                height: 0,
+            },
+        ))
+    });
+    let (world, world_buf) = &mut *off_screen;
+    world_buf.width = tiles.width;
+    world_buf.height = tiles.height;
+    reset(world, world_buf, tiles.width, tiles.height);
+    world.claim(0, tiles.screen());
+    let mut visible = std::mem::take(&mut world.visible);
+    visible.clear();
+    index.query(tiles.world(), &mut visible);
+    for &entity in &visible {
+        let Ok(texture) = query.get(entity) else {
+            continue;
+        };
+        if !tiles.shows(texture) {
             continue;
         }
-        // Check the zdepth for all tiles
-        //let start_x = overlap.min.x - camera_rec.min.x;
-        //let end_x = overlap.max.x - camera_rec.max.x;
-        //let start_y = overlap.max.y - camera_rec.max.y;
-        //let end_y = overlap.min.y - camera_rec.min.y;
-
-        // If not autosize, but stretch, the camera dimensions we will normalize onto
-        // the RenderCache, and then finalize by writing to the
-        // TerminalDisplayBuffer.
-        let start_x;
-        let start_y;
-        let end_x;
-        let end_y;
-        if camera.settings_ref().stretch() {
-            let norm_min = normalize_point(overlap.min, camera_rec.max, camera_rec.min);
-            let norm_max = normalize_point(overlap.max, camera_rec.max, camera_rec.min);
-            (start_x, start_y) = normalized_point_to_tile(norm_min, buf_width, buf_height);
-            (end_x, end_y) = normalized_point_to_tile(norm_max, buf_width, buf_height);
-        } else {
-            let tile_min = overlap.min - camera_rec.min;
-            let tile_max = overlap.max - camera_rec.min;
-            (start_x, start_y) = (tile_min.x as u16, tile_min.y as u16);
-            //  Cap them to the buffer size.
-            (end_x, end_y) = (
-                min(tile_max.x as u16, buf_width),
-                min(tile_max.y as u16, buf_height),
+        if let Some(bounds) = tiles.screen_bounds(texture.0, false) {
+            rasterize(
+                world,
+                world_buf,
+                (0, &tiles),
+                entity,
+                texture,
+                bounds,
+                tiles.screen(),
             );
         }
+    }
+    world.visible = visible;
 
-        // Iterate through the sections that we're actually updating
-        for row in start_y..end_y {
-            for col in start_x..end_x {
-                let tile = display_buf
-                    .0
-                    .buf
-                    .get_mut((col + row * buf_width) as usize)
-                    .unwrap();
-                if *tile == ' ' {
-                    *tile = texture.texture;
+    let block = view.block;
+    let priority = |tile: &Tile| {
+        tile.entity
+            .and_then(|entity| priorities.get(entity).ok())
+            .copied()
+            .unwrap_or_default()
+    };
+    let mut candidates: Vec<(Tile, Cell)> = Vec::new();
+    for row in 0..view.height as u32 {
+        for col in 0..view.width as u32 {
+            candidates.clear();
+            for y in row * block..(row + 1) * block {
+                for x in col * block..(col + 1) * block {
+                    let Some(cell) = world_buf.get(x as u16, y as u16) else {
+                        continue;
+                    };
+                    let tile = *world.tile_mut(x, y);
+                    if tile.entity.is_some() && !cell.is_continuation() {
+                        candidates.push((Tile { view: i, ..tile }, *cell));
+                    }
                 }
             }
+            let highest = |a: &&(Tile, Cell), b: &&(Tile, Cell)| {
+                if a.0.is_above(&b.0) {
+                    Ordering::Greater
+                } else {
+                    Ordering::Less
+                }
+            };
+            let picked = match view.aggregation {
+                GlyphAggregation::HighestZ => candidates.iter().max_by(highest),
+                GlyphAggregation::Priority => candidates
+                    .iter()
+                    .max_by(|a, b| priority(&a.0).cmp(&priority(&b.0)).then(highest(a, b))),
+                GlyphAggregation::MostCommon => {
+                    let count = |glyph: char| {
+                        candidates
+                            .iter()
+                            .filter(|(_, cell)| cell.glyph == glyph)
+                            .count()
+                    };
+                    candidates
+                        .iter()
+                        .max_by(|a, b| count(a.1.glyph).cmp(&count(b.1.glyph)).then(highest(a, b)))
+                }
+            };
+            if let Some((tile, cell)) = picked.copied() {
+                let (col, row) = (view.screen().min.x + col, view.screen().min.y + row);
+                draw_cell(cache, display_buf, tile, cell, col, row, view.screen());
+            }
         }
     }
+    cache.off_screen = Some(off_screen);
+}
+
+/// Fill the cells of `bounds` inside `clip` with the texture, skipping any
+/// which are already covered by something above it.
+fn rasterize(
+    cache: &mut RenderCache,
+    display_buf: &mut VirtualDisplayBuffer,
+    (i, view): (usize, &View),
+    entity: Entity,
+    (texture, sprite, screen_space, _, level): TextureItem,
+    bounds: CellRect,
+    clip: CellRect,
+) {
+    let area = bounds.intersect(clip);
+    if area.is_empty() {
+        return;
+    }
+    let screen_space = screen_space.is_some();
+    let below = view.levels_below(level).unwrap_or(0);
+    let depth = Tile {
+        screen_space,
+        below,
+        z_depth: texture.loc_z,
+        entity: Some(entity),
+        view: i,
+    };
+    for row in area.min.y..area.max.y {
+        // Wide glyphs take up two columns so step over their continuation
+        // cell. We step from the rect's own edge so glyphs line up no matter
+        // the clip.
+        let mut col = bounds.min.x;
+        while col < area.max.x {
+            let cell = texture.fragment(sprite, view.local_cell(texture, screen_space, col, row));
+            let glyph_width = cell.map_or(1, |cell| cell.width() as u32);
+            if let Some(mut cell) = cell {
+                // Lower levels are further away.
+                if below > 0 {
+                    cell.style = cell.style.with_attribute(Attribute::Dim);
+                }
+                draw_cell(cache, display_buf, depth, cell, col, row, area);
+            }
+            col += glyph_width;
+        }
+    }
+}
+
+/// Depth test and draw a single glyph at (`col`, `row`), only touching cells
+/// inside `area` which belong to the view drawing it.
+fn draw_cell(
+    cache: &mut RenderCache,
+    display_buf: &mut VirtualDisplayBuffer,
+    depth: Tile,
+    cell: Cell,
+    col: u32,
+    row: u32,
+    area: CellRect,
+) {
+    let glyph_width = cell.width() as usize;
+    // Which of the columns this glyph covers we're in front of.
+    let mut visible = [false; 2];
+    for (half, is_visible) in visible.iter_mut().enumerate().take(glyph_width) {
+        let col = col + half as u32;
+        *is_visible = col >= area.min.x
+            && col < area.max.x
+            && (depth.screen_space || cache.owner(col, row) == depth.view)
+            && depth.is_above(cache.tile_mut(col, row));
+    }
+    if visible == [false; 2] {
+        return;
+    }
+    // Writing over half of a wide glyph blanks its other half, so whatever
+    // is drawn there next should show.
+    let first = col + (!visible[0]) as u32;
+    let last = col + glyph_width as u32 - 1 - (glyph_width > 1 && !visible[1]) as u32;
+    if first > 0
+        && matches!(display_buf.get(first as u16, row as u16), Some(cell) if cell.is_continuation())
+    {
+        *cache.tile_mut(first - 1, row) = Tile::default();
+    }
+    if matches!(display_buf.get(last as u16, row as u16), Some(cell) if cell.width() > 1)
+        && last + 1 < display_buf.width as u32
+    {
+        *cache.tile_mut(last + 1, row) = Tile::default();
+    }
+    if glyph_width == 1 || visible == [true; 2] {
+        display_buf.set(col as u16, row as u16, cell);
+    } else {
+        // Half of a wide glyph can't be drawn, leave a blank in the
+        // half we're in front of.
+        let half = visible[1] as u32;
+        display_buf.set((col + half) as u16, row as u16, Cell::new(' ', cell.style));
+    }
+    for (half, _) in visible.iter().enumerate().filter(|(_, v)| **v) {
+        *cache.tile_mut(col + half as u32, row) = depth;
+    }
+}
+
+/// App rendering to a headless terminal of `width` by `height` cells.
+#[cfg(test)]
+fn headless_app(width: u16, height: u16) -> (App, super::backend::HeadlessHandle) {
+    use super::backend::{HeadlessBackend, Terminal};
+
+    let backend = HeadlessBackend::new(width, height);
+    let handle = backend.handle();
+    let mut app = App::new();
+    app.insert_resource(Terminal::new(backend))
+        .add_plugin(super::TerminalPlugin::default());
+    (app, handle)
+}
+
+/// Rect of a single glyph in the default style.
+#[cfg(test)]
+fn rect(texture: char, loc: Vec2, dim: Vec2, loc_z: f32) -> TextureRect {
+    TextureRect {
+        texture,
+        style: CellStyle::default(),
+        dim,
+        loc,
+        loc_z,
+    }
+}
+
+#[test]
+fn test_render_damaged_regions() {
+    let (mut app, handle) = headless_app(8, 3);
+    app.world
+        .spawn(rect('#', Vec2::new(-3.5, -1.0), Vec2::ONE, 1.0));
+    let dwarf = app
+        .world
+        .spawn(rect('@', Vec2::new(0.5, 0.0), Vec2::ONE, 2.0))
+        .id();
+    app.world
+        .spawn(rect('.', Vec2::ZERO, Vec2::new(8.0, 1.0), 0.0));
+    app.update();
+    app.update();
+    assert_eq!(handle.rows(), vec!["#       ", "....@...", "        "]);
+
+    // Walking over the floor redraws what was underneath.
+    app.world.get_mut::<TextureRect>(dwarf).unwrap().loc.x += 1.0;
+    app.update();
+    assert_eq!(handle.rows(), vec!["#       ", ".....@..", "        "]);
+
+    app.world.despawn(dwarf);
+    app.update();
+    assert_eq!(handle.rows(), vec!["#       ", "........", "        "]);
+}
+
+#[test]
+fn test_render_depth() {
+    let (mut app, handle) = headless_app(4, 1);
+    let row =
+        |texture, x, width, loc_z| rect(texture, Vec2::new(x, 0.0), Vec2::new(width, 1.0), loc_z);
+    app.world.spawn(row('.', 0.0, 4.0, 0.0));
+    // A space is a real glyph, it hides what's below.
+    app.world.spawn(row(' ', -1.5, 1.0, 1.0));
+    // Transparent rects don't hide anything, no matter how high they are.
+    app.world
+        .spawn(row(TextureRect::TRANSPARENT, 0.0, 4.0, 5.0));
+    // Equal depth is broken by entity id.
+    app.world.spawn(row('a', 1.0, 2.0, 2.0));
+    app.world.spawn(row('b', 0.5, 1.0, 2.0));
+    app.update();
+    app.update();
+    assert_eq!(handle.rows(), vec![" .ba"]);
+}
+
+#[test]
+fn test_render_sprite() {
+    use super::sprite::SpriteMode;
+
+    let (mut app, handle) = headless_app(6, 4);
+    app.world
+        .spawn(rect('.', Vec2::ZERO, Vec2::new(6.0, 4.0), 0.0));
+    let boxed = Sprite::from_rows(&["+-+", "| |", "+-+"], CellStyle::default())
+        .with_mode(SpriteMode::NineSlice {
+            left: 1,
+            right: 1,
+            top: 1,
+            bottom: 1,
+        })
+        .with_transparent(' ');
+    let entity = app
+        .world
+        .spawn((
+            rect('#', Vec2::new(0.5, 0.5), Vec2::new(5.0, 3.0), 1.0),
+            boxed,
+        ))
+        .id();
+    app.update();
+    app.update();
+    assert_eq!(handle.rows(), vec!["......", ".+---+", ".|...|", ".+---+"]);
+
+    // Without the sprite the rect falls back to its plain texture.
+    app.world.entity_mut(entity).remove::<Sprite>();
+    app.update();
+    assert_eq!(handle.rows(), vec!["......", ".#####", ".#####", ".#####"]);
+}
+
+#[test]
+fn test_render_screen_space() {
+    use super::camera::MainCamera;
+    use super::screen::{Anchor, ScreenLength};
+
+    let (mut app, handle) = headless_app(6, 3);
+    app.world
+        .spawn(rect('.', Vec2::ZERO, Vec2::new(1000.0, 1000.0), 100.0));
+    app.world
+        .spawn(rect('x', Vec2::new(0.5, 0.5), Vec2::ONE, 200.0));
+    // The status line sits on the bottom row whatever the camera does, and
+    // covers the world below it no matter the z.
+    app.world.spawn((
+        rect('=', Vec2::ZERO, Vec2::ZERO, 0.0),
+        ScreenSpace::new(
+            Anchor::Bottom,
+            ScreenLength::Percent(100.0),
+            ScreenLength::Cells(1),
+        ),
+    ));
+    app.update();
+    app.update();
+    assert_eq!(handle.rows(), vec!["......", "...x..", "======"]);
+
+    app.world
+        .query_filtered::<&mut TerminalCamera2d, With<MainCamera>>()
+        .single_mut(&mut app.world)
+        .move_by(Vec3::new(0.0, 1.0, 0.0));
+    app.update();
+    assert_eq!(handle.rows(), vec!["...x..", "......", "======"]);
+
+    handle.resize(4, 2);
+    app.update();
+    app.update();
+    assert_eq!(handle.rows(), vec!["..x.", "===="]);
 }
 
 #[test]
-fn test_normalize_point() {
-    let min = Vec2::new(0.0, 0.0);
-    let max = Vec2::new(10.0, 10.0);
-
-    let norm_min = normalize_point(min, max, min);
-    assert_eq!(norm_min, Vec2::new(0.0, 0.0));
-    let norm_max = normalize_point(max, max, min);
-    assert_eq!(norm_max, Vec2::new(1.0, 1.0));
+fn test_render_zoom() {
+    use super::camera::MainCamera;
+
+    let (mut app, handle) = headless_app(4, 2);
+    app.world
+        .spawn(rect('.', Vec2::ZERO, Vec2::new(8.0, 4.0), 0.0));
+    app.world
+        .spawn(rect('x', Vec2::new(0.5, 0.5), Vec2::ONE, 1.0));
+    // Both of these end up in the top left cell once zoomed out.
+    app.world
+        .spawn(rect('@', Vec2::new(-3.5, -1.5), Vec2::ONE, 1.0));
+    app.world.spawn((
+        rect('!', Vec2::new(-2.5, -1.5), Vec2::ONE, 0.5),
+        ZoomPriority(1),
+    ));
+    app.update();
+    app.update();
+    assert_eq!(handle.rows(), vec!["....", "..x."]);
+
+    let mut set_zoom = |zoom, aggregation| {
+        let mut camera = app
+            .world
+            .query_filtered::<&mut TerminalCamera2d, With<MainCamera>>()
+            .single_mut(&mut app.world);
+        camera.set_zoom(zoom);
+        camera.settings_mut().set_aggregation(aggregation);
+        app.update();
+        handle.rows()
+    };
+    // A world tile spans 2x2 cells.
     assert_eq!(
-        normalize_point(Vec2::new(5.0, 5.0), max, min),
-        Vec2::new(0.5, 0.5)
+        set_zoom(Zoom::In(2), GlyphAggregation::HighestZ),
+        vec!["....", "..xx"]
+    );
+    // Each cell covers 2x2 tiles, showing the whole floor.
+    assert_eq!(
+        set_zoom(Zoom::Out(2), GlyphAggregation::HighestZ),
+        vec!["@...", "..x."]
+    );
+    assert_eq!(
+        set_zoom(Zoom::Out(2), GlyphAggregation::MostCommon),
+        vec!["....", "...."]
     );
-
-    let min = Vec2::new(0.0, 0.0);
-    let max = Vec2::new(10.0, 20.0);
     assert_eq!(
-        normalize_point(Vec2::new(5.0, 5.0), max, min),
-        Vec2::new(0.5, 0.25)
+        set_zoom(Zoom::Out(2), GlyphAggregation::Priority),
+        vec!["!...", "..x."]
     );
 }
 
 #[test]
-fn test_normalize_point_to_tile() {
+fn test_render_viewports() {
+    use super::screen::{Anchor, ScreenLength};
+
+    let (mut app, handle) = headless_app(8, 4);
+    app.world
+        .spawn(rect('.', Vec2::ZERO, Vec2::new(100.0, 100.0), 0.0));
+    let dwarf = app
+        .world
+        .spawn(rect('@', Vec2::new(0.5, 0.5), Vec2::ONE, 1.0))
+        .id();
+    // Only the minimap sees the marker.
+    app.world.spawn((
+        rect('*', Vec2::new(-9.5, -9.5), Vec2::ONE, 1.0),
+        RenderLayers::layer(1),
+    ));
+    // A 3x2 minimap in the top right, sitting over the main map.
+    let minimap = app
+        .world
+        .spawn(
+            TerminalCamera2d::new(Vec2::ZERO, Vec3::new(-9.0, -9.0, 0.0))
+                .with_viewport(ScreenSpace::new(
+                    Anchor::TopRight,
+                    ScreenLength::Cells(3),
+                    ScreenLength::Cells(2),
+                ))
+                .with_layers(RenderLayers::layer(0).with(1))
+                .with_order(1),
+        )
+        .id();
+    app.update();
+    app.update();
     assert_eq!(
-        normalized_point_to_tile(Vec2::new(0.0, 0.0), 10, 10),
-        (0u16, 0u16)
+        handle.rows(),
+        vec![".....*..", "........", "....@...", "........"]
     );
+
+    app.world.get_mut::<TextureRect>(dwarf).unwrap().loc.x += 2.0;
+    app.update();
     assert_eq!(
-        normalized_point_to_tile(Vec2::new(1.0, 1.0), 10, 10),
-        (10u16, 10u16)
+        handle.rows(),
+        vec![".....*..", "........", "......@.", "........"]
     );
+
+    // The minimap hides the main map even where it has nothing to show.
+    app.world
+        .get_mut::<TerminalCamera2d>(minimap)
+        .unwrap()
+        .set_loc(Vec3::new(-100.0, -100.0, 0.0));
+    app.world.get_mut::<TextureRect>(dwarf).unwrap().loc.y -= 2.0;
+    app.update();
     assert_eq!(
-        normalized_point_to_tile(Vec2::new(0.5, 0.5), 10, 10),
-        (5u16, 5u16)
+        handle.rows(),
+        vec![".....   ", ".....   ", "........", "........"]
     );
 
+    app.world.despawn(minimap);
+    app.update();
+    assert_eq!(
+        handle.rows(),
+        vec!["......@.", "........", "........", "........"]
+    );
+
+    // A zoomed out minimap redraws its own viewport when the dwarf moves,
+    // the main map only redraws where the dwarf was and is.
+    app.world.spawn(
+        TerminalCamera2d::new(Vec2::ZERO, Vec3::new(2.0, -1.0, 0.0))
+            .with_viewport(ScreenSpace::new(
+                Anchor::BottomLeft,
+                ScreenLength::Cells(2),
+                ScreenLength::Cells(1),
+            ))
+            .with_zoom(Zoom::Out(2))
+            .with_order(1),
+    );
+    app.update();
+    assert_eq!(
+        handle.rows(),
+        vec!["......@.", "........", "........", ".@......"]
+    );
+    let marker = Cell::new('Z', CellStyle::default());
+    app.world
+        .resource_mut::<TerminalDisplayBuffer>()
+        .virtual_frame_mut()
+        .set(7, 1, marker);
+    app.world.get_mut::<TextureRect>(dwarf).unwrap().loc.x -= 2.0;
+    app.update();
+    assert_eq!(
+        handle.rows(),
+        vec!["....@...", ".......Z", "........", "@......."]
+    );
+}
+
+#[test]
+fn test_render_levels() {
+    use super::camera::MainCamera;
+
+    let (mut app, handle) = headless_app(4, 1);
+    let row =
+        |texture, x, width, loc_z| rect(texture, Vec2::new(x, 0.0), Vec2::new(width, 1.0), loc_z);
+    // Half a floor, a barrel on the level below and a cellar under that.
+    app.world.spawn((row('#', -1.0, 2.0, 0.0), ZLevel(1)));
+    app.world.spawn((row('o', 1.5, 1.0, 0.0), ZLevel(0)));
+    app.world.spawn((row('.', 0.0, 4.0, 5.0), ZLevel(-1)));
+    // Rects without a level are on every level.
+    app.world.spawn(row('@', 0.5, 1.0, 1.0));
+    let camera = app
+        .world
+        .query_filtered::<Entity, With<MainCamera>>()
+        .single(&app.world);
+    let update = |app: &mut App, change: &dyn Fn(&mut TerminalCamera2d)| {
+        change(&mut app.world.get_mut::<TerminalCamera2d>(camera).unwrap());
+        app.update();
+        handle.rows()
+    };
+    let dim = |col| handle.cell(col, 0).unwrap().style.attrs.has(Attribute::Dim);
+    app.update();
+    assert_eq!(update(&mut app, &|_| ()), vec!["  @o"]);
+    assert_eq!(update(&mut app, &|camera| camera.level_up()), vec!["##@ "]);
+
+    // Looking down shows the level below through the open air, dimmed.
+    assert_eq!(
+        update(&mut app, &|camera| camera.settings_mut().set_look_down(1)),
+        vec!["##@o"]
+    );
+    assert!(!dim(0) && !dim(2) && dim(3));
+    // Nearer levels cover further ones whatever their z.
+    assert_eq!(
+        update(&mut app, &|camera| camera.settings_mut().set_look_down(2)),
+        vec!["##@o"]
+    );
     assert_eq!(
-        normalized_point_to_tile(Vec2::new(0.5, 0.5), 10, 20),
-        (5u16, 10u16)
+        update(&mut app, &|camera| camera.level_down()),
+        vec!["..@o"]
     );
+    assert!(dim(0) && !dim(3));
 }
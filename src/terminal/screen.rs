@@ -0,0 +1,142 @@
+use crate::prelude::*;
+
+use super::display::TerminalDisplayBuffer;
+use super::render::TextureRect;
+
+/// Places the entity's [`TextureRect`] on the screen rather than in the
+/// world.
+///
+/// Screen-space rects ignore the camera and are drawn above everything in
+/// the world, their `loc` and `dim` are kept up to date in terminal cells
+/// (origin at the top left) as the terminal is resized. `loc_z` still orders
+/// them against each other.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct ScreenSpace {
+    pub anchor: Anchor,
+    /// Distance from the anchor, positive is right and down.
+    pub offset: (ScreenLength, ScreenLength),
+    pub width: ScreenLength,
+    pub height: ScreenLength,
+}
+
+/// Which point of the screen a rect is pinned to. The same point of the
+/// rect is placed on it, so e.g. `BottomRight` keeps the rect in the bottom
+/// right corner.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Position of the anchor as a fraction of the width and height.
+    fn factor(&self) -> Vec2 {
+        match self {
+            Anchor::TopLeft => Vec2::new(0.0, 0.0),
+            Anchor::Top => Vec2::new(0.5, 0.0),
+            Anchor::TopRight => Vec2::new(1.0, 0.0),
+            Anchor::Left => Vec2::new(0.0, 0.5),
+            Anchor::Center => Vec2::new(0.5, 0.5),
+            Anchor::Right => Vec2::new(1.0, 0.5),
+            Anchor::BottomLeft => Vec2::new(0.0, 1.0),
+            Anchor::Bottom => Vec2::new(0.5, 1.0),
+            Anchor::BottomRight => Vec2::new(1.0, 1.0),
+        }
+    }
+}
+
+/// A length along one axis of the terminal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScreenLength {
+    Cells(i32),
+    /// Percentage of the terminal's width or height.
+    Percent(f32),
+}
+
+impl Default for ScreenLength {
+    fn default() -> Self {
+        Self::Cells(0)
+    }
+}
+
+impl ScreenLength {
+    /// Length in cells, given the length of the terminal along this axis.
+    pub fn resolve(&self, screen: u16) -> f32 {
+        match *self {
+            ScreenLength::Cells(cells) => cells as f32,
+            ScreenLength::Percent(percent) => (screen as f32 * percent / 100.0).round(),
+        }
+    }
+}
+
+impl ScreenSpace {
+    /// A rect of the given size pinned to `anchor`.
+    pub fn new(anchor: Anchor, width: ScreenLength, height: ScreenLength) -> Self {
+        Self {
+            anchor,
+            offset: Default::default(),
+            width,
+            height,
+        }
+    }
+
+    pub fn with_offset(mut self, x: ScreenLength, y: ScreenLength) -> Self {
+        self.offset = (x, y);
+        self
+    }
+
+    /// The cells covered on a terminal of the given size.
+    pub fn resolve(&self, width: u16, height: u16) -> Rect {
+        let screen = Vec2::new(width as f32, height as f32);
+        let size =
+            Vec2::new(self.width.resolve(width), self.height.resolve(height)).max(Vec2::ZERO);
+        let offset = Vec2::new(self.offset.0.resolve(width), self.offset.1.resolve(height));
+        let min = ((screen - size) * self.anchor.factor()).floor() + offset;
+        Rect::from_corners(min, min + size)
+    }
+}
+
+/// Keep screen-space rects where they belong as the terminal changes size.
+pub(super) fn layout_screen_space(
+    display_buf: Res<TerminalDisplayBuffer>,
+    mut last_size: Local<(u16, u16)>,
+    mut query: Query<(Ref<ScreenSpace>, &mut TextureRect)>,
+) {
+    let size = (display_buf.0.width, display_buf.0.height);
+    let resized = *last_size != size;
+    *last_size = size;
+    for (screen_space, mut texture) in query.iter_mut() {
+        if !resized && !screen_space.is_changed() {
+            continue;
+        }
+        let rect = screen_space.resolve(size.0, size.1);
+        // Avoid flagging the rect as changed when nothing moved.
+        if texture.loc != rect.center() || texture.dim != rect.size() {
+            texture.loc = rect.center();
+            texture.dim = rect.size();
+        }
+    }
+}
+
+#[test]
+fn test_screen_space_resolve() {
+    use ScreenLength::{Cells, Percent};
+
+    let status = ScreenSpace::new(Anchor::Bottom, Percent(100.0), Cells(1));
+    assert_eq!(status.resolve(80, 24), Rect::new(0.0, 23.0, 80.0, 24.0));
+
+    let minimap =
+        ScreenSpace::new(Anchor::TopRight, Cells(10), Cells(5)).with_offset(Cells(-1), Cells(1));
+    assert_eq!(minimap.resolve(80, 24), Rect::new(69.0, 1.0, 79.0, 6.0));
+
+    let menu = ScreenSpace::new(Anchor::Center, Percent(50.0), Percent(50.0));
+    assert_eq!(menu.resolve(81, 24), Rect::new(20.0, 6.0, 61.0, 18.0));
+}
@@ -0,0 +1,190 @@
+use bevy::utils::{HashMap, HashSet};
+
+use crate::prelude::*;
+
+use super::render::TextureRect;
+use super::screen::ScreenSpace;
+
+/// Rects spanning more buckets than this are kept aside and checked on every
+/// query, rather than being copied into a huge number of buckets.
+const MAX_BUCKETS_PER_ENTRY: i32 = 256;
+
+/// Grid-bucketed index of where every [`TextureRect`] is in world space, so
+/// lookups only visit the rects near the area of interest rather than every
+/// rect in the world.
+#[derive(Resource)]
+pub struct SpatialIndex {
+    bucket_size: f32,
+    buckets: HashMap<IVec2, Vec<Entity>>,
+    oversized: HashSet<Entity>,
+    entries: HashMap<Entity, Entry>,
+}
+
+struct Entry {
+    rect: Rect,
+    /// Inclusive range of buckets the rect was inserted in, `None` if oversized.
+    buckets: Option<(IVec2, IVec2)>,
+}
+
+impl Default for SpatialIndex {
+    fn default() -> Self {
+        Self::new(16.0)
+    }
+}
+
+impl SpatialIndex {
+    pub fn new(bucket_size: f32) -> Self {
+        Self {
+            bucket_size,
+            buckets: HashMap::default(),
+            oversized: HashSet::default(),
+            entries: HashMap::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<Rect> {
+        self.entries.get(&entity).map(|entry| entry.rect)
+    }
+
+    fn bucket_range(&self, rect: Rect) -> (IVec2, IVec2) {
+        let min = (rect.min / self.bucket_size).floor().as_ivec2();
+        let max = (rect.max / self.bucket_size).floor().as_ivec2();
+        (min, max)
+    }
+
+    /// Insert `entity`, replacing wherever it was before.
+    pub fn insert(&mut self, entity: Entity, rect: Rect) {
+        if let Some(entry) = self.entries.get(&entity) {
+            if entry.rect == rect {
+                return;
+            }
+            self.remove(entity);
+        }
+        let (min, max) = self.bucket_range(rect);
+        let span = (max - min + IVec2::ONE).as_vec2();
+        let buckets = if span.x * span.y > MAX_BUCKETS_PER_ENTRY as f32 {
+            self.oversized.insert(entity);
+            None
+        } else {
+            for y in min.y..=max.y {
+                for x in min.x..=max.x {
+                    self.buckets
+                        .entry(IVec2::new(x, y))
+                        .or_default()
+                        .push(entity);
+                }
+            }
+            Some((min, max))
+        };
+        self.entries.insert(entity, Entry { rect, buckets });
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        let Some(entry) = self.entries.remove(&entity) else {
+            return;
+        };
+        let Some((min, max)) = entry.buckets else {
+            self.oversized.remove(&entity);
+            return;
+        };
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let key = IVec2::new(x, y);
+                if let Some(bucket) = self.buckets.get_mut(&key) {
+                    bucket.retain(|e| *e != entity);
+                    if bucket.is_empty() {
+                        self.buckets.remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collect every entity whose rect overlaps `rect` into `out`, each entity
+    /// is reported once.
+    pub fn query(&self, rect: Rect, out: &mut Vec<Entity>) {
+        let overlaps = |other: Rect| !rect.intersect(other).is_empty();
+        let (min, max) = self.bucket_range(rect);
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                let Some(bucket) = self.buckets.get(&IVec2::new(x, y)) else {
+                    continue;
+                };
+                for entity in bucket {
+                    let entry = &self.entries[entity];
+                    // Rects spanning several buckets are only reported from the
+                    // first one which is part of the query.
+                    let (entry_min, _) = entry.buckets.unwrap();
+                    if entry_min.max(min) == IVec2::new(x, y) && overlaps(entry.rect) {
+                        out.push(*entity);
+                    }
+                }
+            }
+        }
+        out.extend(
+            self.oversized
+                .iter()
+                .filter(|entity| overlaps(self.entries[*entity].rect)),
+        );
+    }
+}
+
+/// Keep the index in sync with the world, rects in [`ScreenSpace`] aren't
+/// part of the world so they're left out.
+pub(super) fn update_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    changed: Query<(Entity, &TextureRect, Option<&ScreenSpace>), Changed<TextureRect>>,
+    added_screen_space: Query<Entity, Added<ScreenSpace>>,
+    mut removed: RemovedComponents<TextureRect>,
+    mut removed_screen_space: RemovedComponents<ScreenSpace>,
+    textures: Query<&TextureRect, Without<ScreenSpace>>,
+) {
+    for entity in removed.iter().chain(added_screen_space.iter()) {
+        index.remove(entity);
+    }
+    for (entity, texture, screen_space) in changed.iter() {
+        if screen_space.is_none() {
+            index.insert(entity, Rect::from_center_size(texture.loc, texture.dim));
+        }
+    }
+    for entity in removed_screen_space.iter() {
+        if let Ok(texture) = textures.get(entity) {
+            index.insert(entity, Rect::from_center_size(texture.loc, texture.dim));
+        }
+    }
+}
+
+#[test]
+fn test_spatial_index_query() {
+    let mut index = SpatialIndex::new(4.0);
+    let small = Entity::from_raw(0);
+    let wide = Entity::from_raw(1);
+    let huge = Entity::from_raw(2);
+    index.insert(small, Rect::new(1.0, 1.0, 2.0, 2.0));
+    index.insert(wide, Rect::new(-10.0, 0.0, 10.0, 1.0));
+    index.insert(huge, Rect::new(-1000.0, -1000.0, 1000.0, 1000.0));
+
+    let mut found = Vec::new();
+    index.query(Rect::new(0.0, 0.0, 8.0, 8.0), &mut found);
+    found.sort();
+    assert_eq!(found, vec![small, wide, huge]);
+
+    found.clear();
+    index.query(Rect::new(20.0, 20.0, 30.0, 30.0), &mut found);
+    assert_eq!(found, vec![huge]);
+
+    index.insert(small, Rect::new(21.0, 21.0, 22.0, 22.0));
+    index.remove(huge);
+    found.clear();
+    index.query(Rect::new(20.0, 20.0, 30.0, 30.0), &mut found);
+    assert_eq!(found, vec![small]);
+    assert_eq!(index.len(), 2);
+}
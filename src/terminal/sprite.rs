@@ -0,0 +1,174 @@
+use crate::prelude::*;
+
+use super::display::{Cell, CellStyle};
+
+/// A grid of glyphs drawn across an entity's [`TextureRect`](super::render::TextureRect)
+/// in place of its single `texture`.
+///
+/// Cells set to `None` are transparent.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct Sprite {
+    width: u16,
+    height: u16,
+    cells: Vec<Option<Cell>>,
+    pub mode: SpriteMode,
+}
+
+/// How a sprite is fit to a rect which isn't the same size as it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpriteMode {
+    /// Repeat the sprite across the rect.
+    #[default]
+    Tile,
+    /// Scale the sprite to cover the rect.
+    Stretch,
+    /// Keep the borders of the given sizes as they are and tile the middle,
+    /// e.g. to draw boxes of any size from a 3x3 sprite.
+    NineSlice {
+        left: u16,
+        right: u16,
+        top: u16,
+        bottom: u16,
+    },
+}
+
+impl Sprite {
+    /// A fully transparent sprite.
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![None; width as usize * height as usize],
+            mode: SpriteMode::default(),
+        }
+    }
+
+    /// Build a sprite from rows of text, all in the same style. Rows shorter
+    /// than the longest are padded with transparent cells.
+    pub fn from_rows(rows: &[&str], style: CellStyle) -> Self {
+        let width = rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut sprite = Self::new(width as u16, rows.len() as u16);
+        for (row, text) in rows.iter().enumerate() {
+            for (col, glyph) in text.chars().enumerate() {
+                sprite.set(col as u16, row as u16, Some(Cell::new(glyph, style)));
+            }
+        }
+        sprite
+    }
+
+    pub fn with_mode(mut self, mode: SpriteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Make every cell drawn with `glyph` transparent.
+    pub fn with_transparent(mut self, glyph: char) -> Self {
+        for cell in self.cells.iter_mut() {
+            if matches!(cell, Some(cell) if cell.glyph == glyph) {
+                *cell = None;
+            }
+        }
+        self
+    }
+
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    pub fn get(&self, col: u16, row: u16) -> Option<Cell> {
+        if col >= self.width || row >= self.height {
+            return None;
+        }
+        self.cells[col as usize + row as usize * self.width as usize]
+    }
+
+    pub fn set(&mut self, col: u16, row: u16, cell: Option<Cell>) {
+        if col >= self.width || row >= self.height {
+            return;
+        }
+        self.cells[col as usize + row as usize * self.width as usize] = cell;
+    }
+
+    /// The cell drawn at `local` in a rect of `size` cells.
+    pub fn sample(&self, local: UVec2, size: UVec2) -> Option<Cell> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        let (width, height) = (self.width as u32, self.height as u32);
+        let (col, row) = match self.mode {
+            SpriteMode::Tile => (local.x % width, local.y % height),
+            SpriteMode::Stretch => (
+                local.x * width / size.x.max(1),
+                local.y * height / size.y.max(1),
+            ),
+            SpriteMode::NineSlice {
+                left,
+                right,
+                top,
+                bottom,
+            } => (
+                nine_slice_axis(local.x, size.x, width, left as u32, right as u32),
+                nine_slice_axis(local.y, size.y, height, top as u32, bottom as u32),
+            ),
+        };
+        self.get(col.min(width - 1) as u16, row.min(height - 1) as u16)
+    }
+}
+
+/// Map `pos` along an axis of length `len` onto a sprite axis of `src_len`,
+/// keeping `start` and `end` cells at either edge and tiling the middle.
+fn nine_slice_axis(pos: u32, len: u32, src_len: u32, start: u32, end: u32) -> u32 {
+    let middle = src_len.saturating_sub(start + end);
+    if pos < start {
+        pos
+    } else if pos + end >= len {
+        src_len.saturating_sub(len - pos)
+    } else if middle == 0 {
+        start.min(src_len - 1)
+    } else {
+        start + (pos - start) % middle
+    }
+}
+
+#[test]
+fn test_sprite_sample() {
+    let style = CellStyle::default();
+    let glyph = |sprite: &Sprite, x, y, w, h| {
+        sprite
+            .sample(UVec2::new(x, y), UVec2::new(w, h))
+            .map(|cell| cell.glyph)
+    };
+
+    let tiled = Sprite::from_rows(&["ab"], style);
+    assert_eq!(glyph(&tiled, 3, 0, 5, 1), Some('b'));
+    assert_eq!(glyph(&tiled, 4, 2, 5, 3), Some('a'));
+
+    let stretched = Sprite::from_rows(&["ab"], style).with_mode(SpriteMode::Stretch);
+    assert_eq!(glyph(&stretched, 1, 0, 4, 1), Some('a'));
+    assert_eq!(glyph(&stretched, 2, 0, 4, 1), Some('b'));
+
+    let boxed = Sprite::from_rows(&["+-+", "| |", "+-+"], style)
+        .with_mode(SpriteMode::NineSlice {
+            left: 1,
+            right: 1,
+            top: 1,
+            bottom: 1,
+        })
+        .with_transparent(' ');
+    let rows: Vec<String> = (0..4)
+        .map(|y| {
+            (0..5)
+                .map(|x| glyph(&boxed, x, y, 5, 4).unwrap_or('.'))
+                .collect()
+        })
+        .collect();
+    assert_eq!(rows, vec!["+---+", "|...|", "|...|", "+---+"]);
+}
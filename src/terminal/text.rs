@@ -0,0 +1,283 @@
+use crate::prelude::*;
+
+use super::display::{glyph_width, Cell, CellStyle};
+use super::render::TextureRect;
+use super::sprite::Sprite;
+
+/// Glyph drawn in place of text which didn't fit.
+pub const ELLIPSIS: char = '…';
+
+/// A run of text drawn in a single style.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub style: CellStyle,
+}
+
+impl TextSpan {
+    pub fn new(text: impl Into<String>, style: CellStyle) -> Self {
+        Self {
+            text: text.into(),
+            style,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+/// Text laid out inside the entity's [`TextureRect`].
+///
+/// The text is drawn through a [`Sprite`] on the same entity which is kept in
+/// sync with the text and the size of the rect, so it's depth tested like any
+/// other rect. Cells the text doesn't cover are transparent unless `fill` is
+/// set.
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct TextBlock {
+    pub spans: Vec<TextSpan>,
+    pub align: TextAlign,
+    /// Break lines between words to fit the width of the rect, otherwise
+    /// lines only break on `'\n'`.
+    pub wrap: bool,
+    /// End lines which don't fit, or the last line when some don't fit, with
+    /// an [`ELLIPSIS`] rather than just cutting them off.
+    pub ellipsis: bool,
+    /// Style of the blanks around the text.
+    pub fill: Option<CellStyle>,
+}
+
+type Glyph = (char, CellStyle);
+
+impl TextBlock {
+    pub fn new(text: impl Into<String>, style: CellStyle) -> Self {
+        Self {
+            spans: vec![TextSpan::new(text, style)],
+            ..Default::default()
+        }
+    }
+
+    pub fn with_span(mut self, text: impl Into<String>, style: CellStyle) -> Self {
+        self.spans.push(TextSpan::new(text, style));
+        self
+    }
+
+    pub fn with_align(mut self, align: TextAlign) -> Self {
+        self.align = align;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn with_ellipsis(mut self, ellipsis: bool) -> Self {
+        self.ellipsis = ellipsis;
+        self
+    }
+
+    pub fn with_fill(mut self, fill: CellStyle) -> Self {
+        self.fill = Some(fill);
+        self
+    }
+
+    /// Lay the text out into a sprite of the given size.
+    pub fn layout(&self, width: u16, height: u16) -> Sprite {
+        let mut sprite = Sprite::new(width, height);
+        if let Some(fill) = self.fill {
+            for row in 0..height {
+                for col in 0..width {
+                    sprite.set(col, row, Some(Cell::new(' ', fill)));
+                }
+            }
+        }
+
+        let mut paragraphs = vec![Vec::new()];
+        for span in self.spans.iter() {
+            for glyph in span.text.chars() {
+                match glyph {
+                    '\n' => paragraphs.push(Vec::new()),
+                    glyph if glyph.is_control() => (),
+                    glyph => paragraphs.last_mut().unwrap().push((glyph, span.style)),
+                }
+            }
+        }
+        let mut lines = Vec::new();
+        for paragraph in paragraphs {
+            if self.wrap {
+                wrap_line(&paragraph, width, &mut lines);
+            } else {
+                lines.push(paragraph);
+            }
+        }
+        let truncated = lines.len() > height as usize;
+        lines.truncate(height as usize);
+
+        let last = lines.len().saturating_sub(1);
+        for (row, mut line) in lines.into_iter().enumerate() {
+            let overflows = line_width(&line) > width as u32;
+            if overflows || (truncated && row == last) {
+                fit_line(&mut line, width, self.ellipsis);
+            }
+            let spare = width as u32 - line_width(&line);
+            let mut col = match self.align {
+                TextAlign::Left => 0,
+                TextAlign::Center => spare / 2,
+                TextAlign::Right => spare,
+            } as u16;
+            for (glyph, style) in line {
+                sprite.set(col, row as u16, Some(Cell::new(glyph, style)));
+                // Leave the second column of wide glyphs alone, the renderer
+                // steps over it.
+                col += glyph_width(glyph);
+            }
+        }
+        sprite
+    }
+}
+
+fn line_width(line: &[Glyph]) -> u32 {
+    line.iter()
+        .map(|(glyph, _)| glyph_width(*glyph) as u32)
+        .sum()
+}
+
+/// Break `glyphs` into lines no wider than `width`, between words where
+/// possible.
+fn wrap_line(glyphs: &[Glyph], width: u16, lines: &mut Vec<Vec<Glyph>>) {
+    let width = width as u32;
+    let mut line = Vec::new();
+    let mut current = 0;
+    let mut idx = 0;
+    let mut first = true;
+    while idx < glyphs.len() || first {
+        let space_start = idx;
+        while idx < glyphs.len() && glyphs[idx].0 == ' ' {
+            idx += 1;
+        }
+        let word_start = idx;
+        while idx < glyphs.len() && glyphs[idx].0 != ' ' {
+            idx += 1;
+        }
+        let spaces = &glyphs[space_start..word_start];
+        let word = &glyphs[word_start..idx];
+        let word_width = line_width(word);
+
+        if !line.is_empty() && current + spaces.len() as u32 + word_width > width {
+            lines.push(std::mem::take(&mut line));
+            current = 0;
+        }
+        // Spaces where a line was broken are dropped, but indentation at the
+        // start of a paragraph is kept.
+        if !line.is_empty() || first {
+            line.extend_from_slice(spaces);
+            current += spaces.len() as u32;
+        }
+        for glyph in word {
+            let glyph_width = glyph_width(glyph.0) as u32;
+            // Words too long for a line of their own are broken anywhere.
+            if !line.is_empty() && current + glyph_width > width {
+                lines.push(std::mem::take(&mut line));
+                current = 0;
+            }
+            line.push(*glyph);
+            current += glyph_width;
+        }
+        first = false;
+    }
+    lines.push(line);
+}
+
+/// Cut `line` down to fit in `width`, ending it with an ellipsis if asked.
+fn fit_line(line: &mut Vec<Glyph>, width: u16, ellipsis: bool) {
+    let room = if ellipsis {
+        width.saturating_sub(1)
+    } else {
+        width
+    } as u32;
+    let mut current = 0;
+    let keep = line
+        .iter()
+        .take_while(|(glyph, _)| {
+            current += glyph_width(*glyph) as u32;
+            current <= room
+        })
+        .count();
+    let style = line.get(keep).or(line.last()).map(|(_, style)| *style);
+    line.truncate(keep);
+    if ellipsis && width > 0 {
+        while matches!(line.last(), Some((' ', _))) {
+            line.pop();
+        }
+        line.push((ELLIPSIS, style.unwrap_or_default()));
+    }
+}
+
+/// Keep the sprite of every text entity in sync with its text and rect size.
+pub(super) fn layout_text(
+    mut cmd: Commands,
+    mut query: Query<(Entity, Ref<TextBlock>, &TextureRect, Option<&mut Sprite>)>,
+) {
+    for (entity, text, texture, sprite) in query.iter_mut() {
+        let size = texture.dim.ceil().as_uvec2();
+        let (width, height) = (size.x as u16, size.y as u16);
+        match sprite {
+            Some(sprite)
+                if !text.is_changed() && sprite.width() == width && sprite.height() == height => {}
+            Some(mut sprite) => *sprite = text.layout(width, height),
+            None => {
+                cmd.entity(entity).insert(text.layout(width, height));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_text_layout() {
+    let style = CellStyle::default();
+    let rows = |text: &TextBlock, width: u16, height: u16| -> Vec<String> {
+        let sprite = text.layout(width, height);
+        (0..height)
+            .map(|row| {
+                (0..width)
+                    .map(|col| sprite.get(col, row).map_or('.', |cell| cell.glyph))
+                    .collect()
+            })
+            .collect()
+    };
+
+    let text = TextBlock::new("the quick brown fox", style).with_wrap(true);
+    assert_eq!(
+        rows(&text, 9, 3),
+        vec!["the quick", "brown fox", "........."]
+    );
+    assert_eq!(
+        rows(&text.clone().with_align(TextAlign::Right), 6, 3),
+        vec!["...the", ".quick", ".brown"]
+    );
+    assert_eq!(
+        rows(&text.clone().with_ellipsis(true), 6, 2),
+        vec!["the...", "quick…"]
+    );
+    assert_eq!(
+        rows(&text.clone().with_align(TextAlign::Center), 7, 1),
+        vec!["..the.."]
+    );
+
+    // Without wrapping long lines are cut off.
+    let text = TextBlock::new("dwarf", style).with_ellipsis(true);
+    assert_eq!(rows(&text, 4, 1), vec!["dwa…"]);
+    assert_eq!(rows(&text.with_ellipsis(false), 4, 1), vec!["dwar"]);
+
+    // Spans keep their own style.
+    let bold = style.with_attribute(crossterm::style::Attribute::Bold);
+    let text = TextBlock::new("hp: ", style).with_span("10", bold);
+    let sprite = text.layout(6, 1);
+    assert_eq!(sprite.get(3, 0).unwrap().style, style);
+    assert_eq!(sprite.get(4, 0).unwrap().style, bold);
+}
@@ -0,0 +1,157 @@
+use crate::prelude::*;
+
+/// How much room a widget asks for along its parent's [`FlexDirection`].
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    /// Exactly this many cells.
+    Length(u16),
+    /// A percentage of the parent.
+    Percent(u16),
+    /// At least this many cells, growing like `Fill(1)` when there's room.
+    Min(u16),
+    /// Grow like `Fill(1)`, but never past this many cells.
+    Max(u16),
+    /// Share whatever is left over with the other growing widgets, in
+    /// proportion to the weight.
+    Fill(u16),
+}
+
+impl Default for Constraint {
+    fn default() -> Self {
+        Self::Fill(1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FlexDirection {
+    /// Children are stacked top to bottom.
+    #[default]
+    Column,
+    /// Children are placed left to right.
+    Row,
+}
+
+/// Lays out a widget's children one after the other, sized by their
+/// [`Constraint`]s.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Flex {
+    pub direction: FlexDirection,
+    /// Cells left between children.
+    pub gap: u16,
+    /// Cells left blank inside the widget's edges.
+    pub padding: u16,
+}
+
+impl Flex {
+    pub fn new(direction: FlexDirection) -> Self {
+        Self {
+            direction,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_gap(mut self, gap: u16) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    pub fn with_padding(mut self, padding: u16) -> Self {
+        self.padding = padding;
+        self
+    }
+}
+
+/// Split `total` cells between `constraints`, returning the offset and length
+/// of each. Fixed sizes are handed out first and the rest is shared by weight,
+/// anything which doesn't fit is cut off at the end.
+pub fn split(total: u16, gap: u16, constraints: &[Constraint]) -> Vec<(u16, u16)> {
+    let count = constraints.len();
+    if count == 0 {
+        return Vec::new();
+    }
+    let gaps = gap as u32 * (count as u32 - 1);
+    let available = (total as u32).saturating_sub(gaps);
+
+    let mut sizes = vec![0u32; count];
+    let mut weights = vec![0u32; count];
+    let mut caps = vec![u32::MAX; count];
+    for (idx, constraint) in constraints.iter().enumerate() {
+        match *constraint {
+            Constraint::Length(len) => sizes[idx] = len as u32,
+            Constraint::Percent(percent) => sizes[idx] = total as u32 * percent as u32 / 100,
+            Constraint::Min(len) => {
+                sizes[idx] = len as u32;
+                weights[idx] = 1;
+            }
+            Constraint::Max(len) => {
+                weights[idx] = 1;
+                caps[idx] = len as u32;
+            }
+            Constraint::Fill(weight) => weights[idx] = weight as u32,
+        }
+    }
+
+    let mut remaining = available.saturating_sub(sizes.iter().sum());
+    let mut growing: Vec<usize> = (0..count).filter(|idx| weights[*idx] > 0).collect();
+    while remaining > 0 && !growing.is_empty() {
+        let total_weight: u32 = growing.iter().map(|idx| weights[*idx]).sum();
+        let share = |idx: usize| remaining * weights[idx] / total_weight;
+        // Anything whose share takes it past its cap gets the cap and the rest
+        // is shared again between the others.
+        let capped: Vec<usize> = growing
+            .iter()
+            .copied()
+            .filter(|idx| sizes[*idx] + share(*idx) >= caps[*idx])
+            .collect();
+        if capped.is_empty() {
+            let mut given = 0;
+            for idx in growing.iter() {
+                sizes[*idx] += share(*idx);
+                given += share(*idx);
+            }
+            // Hand out what was lost to rounding from the start.
+            for idx in growing.iter().take((remaining - given) as usize) {
+                sizes[*idx] += 1;
+            }
+            break;
+        }
+        for idx in capped.iter() {
+            remaining -= caps[*idx] - sizes[*idx];
+            sizes[*idx] = caps[*idx];
+        }
+        growing.retain(|idx| !capped.contains(idx));
+    }
+
+    let mut offset = 0u32;
+    sizes
+        .into_iter()
+        .map(|size| {
+            let start = offset.min(total as u32);
+            let len = size.min(total as u32 - start);
+            offset += size + gap as u32;
+            (start as u16, len as u16)
+        })
+        .collect()
+}
+
+#[test]
+fn test_split() {
+    use Constraint::*;
+
+    assert_eq!(split(10, 0, &[Length(3), Fill(1)]), vec![(0, 3), (3, 7)]);
+    assert_eq!(
+        split(10, 1, &[Fill(1), Fill(1), Fill(2)]),
+        vec![(0, 2), (3, 2), (6, 4)]
+    );
+    assert_eq!(
+        split(20, 0, &[Percent(50), Max(3), Min(2)]),
+        vec![(0, 10), (10, 3), (13, 7)]
+    );
+    // Rounding leftovers go to the first growing widgets.
+    assert_eq!(split(5, 0, &[Fill(1), Fill(1)]), vec![(0, 3), (3, 2)]);
+    // Too little room cuts off the end.
+    assert_eq!(
+        split(4, 0, &[Length(3), Length(3), Fill(1)]),
+        vec![(0, 3), (3, 1), (4, 0)]
+    );
+}
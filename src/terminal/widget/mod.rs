@@ -0,0 +1,925 @@
+//! Retained-mode widgets drawn in screen space.
+//!
+//! Each widget is an entity with a [`Widget`], laid out inside its parent by
+//! [`Flex`] and [`Constraint`] and drawn into a [`Sprite`] on the entity.
+//! Root widgets (those without a parent) are placed by their [`ScreenSpace`].
+//! Keyboard input goes to the widget on top of the [`FocusStack`], tab and
+//! shift-tab move focus between the widgets of the same root.
+
+mod layout;
+
+pub use self::layout::{split, Constraint, Flex, FlexDirection};
+
+use bevy::input::keyboard::ButtonState;
+use crossterm::style::Attribute;
+
+use crate::prelude::*;
+
+use super::display::{glyph_width, Cell, CellStyle};
+use super::focus::{self, FocusStack, FocusedInput};
+use super::input::TerminalKeyInput;
+use super::render::TextureRect;
+use super::screen::{self, Anchor, ScreenLength, ScreenSpace};
+use super::sprite::Sprite;
+use super::text::{self, TextBlock};
+
+#[derive(Default)]
+pub struct TerminalWidgetPlugin();
+
+impl Plugin for TerminalWidgetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WidgetTheme>()
+            .add_event::<WidgetEvent>()
+            .add_system(
+                handle_widget_input
+                    .after(focus::route_input)
+                    .before(layout_widgets),
+            )
+            .add_system(
+                layout_widgets
+                    .after(screen::layout_screen_space)
+                    .before(draw_widgets),
+            )
+            .add_system(draw_widgets.before(text::layout_text));
+    }
+}
+
+/// Sent when the user interacts with a widget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WidgetEvent {
+    /// A button was pressed.
+    Pressed(Entity),
+    /// A checkbox was ticked or unticked.
+    Toggled(Entity, bool),
+    /// The selected row of a list or table moved.
+    Selected(Entity, usize),
+    /// Enter was pressed on a row of a list or table.
+    Activated(Entity, usize),
+    /// The value of a text input was changed.
+    Edited(Entity),
+    /// Enter was pressed in a text input.
+    Submitted(Entity, String),
+}
+
+/// Styles used to draw every widget.
+#[derive(Resource, Clone, Debug)]
+pub struct WidgetTheme {
+    pub text: CellStyle,
+    pub border: CellStyle,
+    pub focused_border: CellStyle,
+    pub header: CellStyle,
+    /// The selected row of a list or table, or a focused button.
+    pub selected: CellStyle,
+    /// The selected row of a list or table which doesn't have focus.
+    pub selected_unfocused: CellStyle,
+    pub bar_filled: CellStyle,
+    pub bar_empty: CellStyle,
+    pub placeholder: CellStyle,
+}
+
+impl Default for WidgetTheme {
+    fn default() -> Self {
+        let text = CellStyle::default();
+        Self {
+            text,
+            border: text,
+            focused_border: text.with_attribute(Attribute::Bold),
+            header: text.with_attribute(Attribute::Bold),
+            selected: text.with_attribute(Attribute::Reverse),
+            selected_unfocused: text.with_attribute(Attribute::Underlined),
+            bar_filled: text,
+            bar_empty: text.with_attribute(Attribute::Dim),
+            placeholder: text.with_attribute(Attribute::Dim),
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, PartialEq)]
+pub enum Widget {
+    Panel(Panel),
+    Label(TextBlock),
+    List(List),
+    Table(Table),
+    ProgressBar(ProgressBar),
+    Button(Button),
+    Checkbox(Checkbox),
+    TextInput(TextInput),
+}
+
+/// Bordered box, optionally titled, which its children are laid out inside.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Panel {
+    pub title: Option<String>,
+}
+
+/// Scrollable list of rows, one of which is selected.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct List {
+    pub items: Vec<String>,
+    pub selected: usize,
+    /// First row shown.
+    pub scroll: usize,
+}
+
+/// Like a [`List`] but with a header and columns sized by [`Constraint`]s.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Table {
+    pub columns: Vec<(String, Constraint)>,
+    pub rows: Vec<Vec<String>>,
+    pub selected: usize,
+    /// First row shown.
+    pub scroll: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProgressBar {
+    /// How full the bar is, from 0 to 1.
+    pub value: f32,
+    /// Drawn centered over the bar.
+    pub label: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Button {
+    pub label: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Checkbox {
+    pub label: String,
+    pub checked: bool,
+}
+
+/// Single line of editable text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextInput {
+    pub value: String,
+    /// Position of the cursor in chars.
+    pub cursor: usize,
+    /// Shown when the value is empty.
+    pub placeholder: String,
+}
+
+impl Widget {
+    pub fn panel(title: impl Into<String>) -> Self {
+        Self::Panel(Panel {
+            title: Some(title.into()),
+        })
+    }
+
+    pub fn label(text: impl Into<String>) -> Self {
+        Self::Label(TextBlock::new(text, CellStyle::default()).with_wrap(true))
+    }
+
+    pub fn list<T: Into<String>>(items: impl IntoIterator<Item = T>) -> Self {
+        Self::List(List {
+            items: items.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        })
+    }
+
+    pub fn progress_bar(value: f32) -> Self {
+        Self::ProgressBar(ProgressBar { value, label: None })
+    }
+
+    pub fn button(label: impl Into<String>) -> Self {
+        Self::Button(Button {
+            label: label.into(),
+        })
+    }
+
+    pub fn checkbox(label: impl Into<String>, checked: bool) -> Self {
+        Self::Checkbox(Checkbox {
+            label: label.into(),
+            checked,
+        })
+    }
+
+    pub fn text_input(placeholder: impl Into<String>) -> Self {
+        Self::TextInput(TextInput {
+            placeholder: placeholder.into(),
+            ..Default::default()
+        })
+    }
+
+    /// Whether the widget does anything with keyboard input.
+    pub fn is_focusable(&self) -> bool {
+        !matches!(
+            self,
+            Widget::Panel(_) | Widget::Label(_) | Widget::ProgressBar(_)
+        )
+    }
+
+    /// Cells lost around the edges of the widget before its children are
+    /// laid out.
+    fn inset(&self) -> u16 {
+        match self {
+            Widget::Panel(_) => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Everything needed to spawn a widget.
+#[derive(Bundle)]
+pub struct WidgetBundle {
+    pub widget: Widget,
+    pub texture: TextureRect,
+    pub screen_space: ScreenSpace,
+    pub constraint: Constraint,
+}
+
+impl WidgetBundle {
+    pub fn new(widget: Widget) -> Self {
+        Self {
+            widget,
+            texture: TextureRect {
+                texture: TextureRect::TRANSPARENT,
+                style: CellStyle::default(),
+                dim: Vec2::ZERO,
+                loc: Vec2::ZERO,
+                loc_z: 0.0,
+            },
+            screen_space: ScreenSpace::new(
+                Anchor::TopLeft,
+                ScreenLength::Cells(0),
+                ScreenLength::Cells(0),
+            ),
+            constraint: Constraint::default(),
+        }
+    }
+
+    /// Where a root widget goes on the screen, widgets with a parent are
+    /// placed by it instead.
+    pub fn with_screen_space(mut self, screen_space: ScreenSpace) -> Self {
+        self.screen_space = screen_space;
+        self
+    }
+
+    pub fn with_constraint(mut self, constraint: Constraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    /// Depth of a root widget, its children are drawn just above it.
+    pub fn with_z(mut self, z: f32) -> Self {
+        self.texture.loc_z = z;
+        self
+    }
+}
+
+/// Scroll offset which keeps `selected` in view, moving as little as possible
+/// from `scroll`.
+fn scroll_to(scroll: usize, selected: usize, height: usize) -> usize {
+    if height == 0 {
+        return scroll;
+    }
+    if selected < scroll {
+        selected
+    } else if selected >= scroll + height {
+        selected + 1 - height
+    } else {
+        scroll
+    }
+}
+
+/// Move a selection of `len` rows by `delta`, clamped to the ends.
+fn move_selection(selected: usize, delta: isize, len: usize) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    (selected as isize + delta).clamp(0, len as isize - 1) as usize
+}
+
+impl Widget {
+    /// React to a key press while focused.
+    fn handle_key(
+        &mut self,
+        entity: Entity,
+        key: KeyCode,
+        height: u16,
+        events: &mut EventWriter<WidgetEvent>,
+    ) {
+        let page = height.max(1) as isize;
+        let delta = match key {
+            KeyCode::Up => Some(-1),
+            KeyCode::Down => Some(1),
+            KeyCode::PageUp => Some(-page),
+            KeyCode::PageDown => Some(page),
+            KeyCode::Home => Some(isize::MIN / 2),
+            KeyCode::End => Some(isize::MAX / 2),
+            _ => None,
+        };
+        match self {
+            Widget::List(List {
+                items: rows,
+                selected,
+                scroll,
+            }) => {
+                handle_rows(
+                    entity,
+                    key,
+                    delta,
+                    rows.len(),
+                    height,
+                    selected,
+                    scroll,
+                    events,
+                );
+            }
+            Widget::Table(Table {
+                rows,
+                selected,
+                scroll,
+                ..
+            }) => {
+                // The header takes up the first row.
+                let height = height.saturating_sub(1);
+                handle_rows(
+                    entity,
+                    key,
+                    delta,
+                    rows.len(),
+                    height,
+                    selected,
+                    scroll,
+                    events,
+                );
+            }
+            Widget::Button(_) => {
+                if matches!(key, KeyCode::Return | KeyCode::Space) {
+                    events.send(WidgetEvent::Pressed(entity));
+                }
+            }
+            Widget::Checkbox(checkbox) => {
+                if matches!(key, KeyCode::Return | KeyCode::Space) {
+                    checkbox.checked = !checkbox.checked;
+                    events.send(WidgetEvent::Toggled(entity, checkbox.checked));
+                }
+            }
+            Widget::TextInput(input) => input.handle_key(entity, key, events),
+            Widget::Panel(_) | Widget::Label(_) | Widget::ProgressBar(_) => (),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_rows(
+    entity: Entity,
+    key: KeyCode,
+    delta: Option<isize>,
+    len: usize,
+    height: u16,
+    selected: &mut usize,
+    scroll: &mut usize,
+    events: &mut EventWriter<WidgetEvent>,
+) {
+    if let Some(delta) = delta {
+        let moved = move_selection(*selected, delta, len);
+        if moved != *selected {
+            *selected = moved;
+            events.send(WidgetEvent::Selected(entity, moved));
+        }
+        *scroll = scroll_to(*scroll, *selected, height as usize);
+    } else if key == KeyCode::Return && *selected < len {
+        events.send(WidgetEvent::Activated(entity, *selected));
+    }
+}
+
+impl TextInput {
+    fn handle_key(&mut self, entity: Entity, key: KeyCode, events: &mut EventWriter<WidgetEvent>) {
+        let len = self.value.chars().count();
+        self.cursor = self.cursor.min(len);
+        let byte_idx = |value: &str, cursor: usize| {
+            value
+                .char_indices()
+                .nth(cursor)
+                .map_or(value.len(), |(idx, _)| idx)
+        };
+        match key {
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(len),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = len,
+            KeyCode::Back if self.cursor > 0 => {
+                self.cursor -= 1;
+                self.value.remove(byte_idx(&self.value, self.cursor));
+                events.send(WidgetEvent::Edited(entity));
+            }
+            KeyCode::Delete if self.cursor < len => {
+                self.value.remove(byte_idx(&self.value, self.cursor));
+                events.send(WidgetEvent::Edited(entity));
+            }
+            KeyCode::Return => events.send(WidgetEvent::Submitted(entity, self.value.clone())),
+            _ => (),
+        }
+    }
+
+    /// Type `glyph` at the cursor, the field is a single line so control
+    /// characters (including pasted newlines) are dropped.
+    fn insert(&mut self, entity: Entity, glyph: char, events: &mut EventWriter<WidgetEvent>) {
+        if glyph.is_control() {
+            return;
+        }
+        self.cursor = self.cursor.min(self.value.chars().count());
+        let byte_idx = self
+            .value
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.value.len(), |(idx, _)| idx);
+        self.value.insert(byte_idx, glyph);
+        self.cursor += 1;
+        events.send(WidgetEvent::Edited(entity));
+    }
+}
+
+fn handle_widget_input(
+    mut input: EventReader<FocusedInput>,
+    mut widgets: Query<(&mut Widget, &TextureRect)>,
+    mut events: EventWriter<WidgetEvent>,
+) {
+    // Keys and text are applied in the order they were typed.
+    for event in input.iter() {
+        let Some(entity) = event.target else {
+            continue;
+        };
+        let Ok((mut widget, texture)) = widgets.get_mut(entity) else {
+            continue;
+        };
+        match event.input {
+            TerminalKeyInput::Key(key) if key.state == ButtonState::Pressed => {
+                if let Some(key) = key.key_code {
+                    widget.handle_key(entity, key, texture.dim.y as u16, &mut events);
+                }
+            }
+            TerminalKeyInput::Key(_) => (),
+            TerminalKeyInput::Char(char) => {
+                if let Widget::TextInput(input) = widget.as_mut() {
+                    input.insert(entity, char, &mut events);
+                }
+            }
+        }
+    }
+}
+
+/// Widgets which are placed on the screen rather than by a parent.
+type RootWidget = (With<Widget>, Without<Parent>);
+
+/// Place the children of every widget inside it.
+fn layout_widgets(
+    mut cmd: Commands,
+    roots: Query<(Entity, &TextureRect), RootWidget>,
+    containers: Query<(Option<&Widget>, Option<&Flex>, &Children)>,
+    constraints: Query<&Constraint>,
+    mut nodes: Query<(&mut TextureRect, Option<&mut ScreenSpace>), With<Parent>>,
+) {
+    let mut stack: Vec<(Entity, Rect, f32)> = roots
+        .iter()
+        .map(|(entity, texture)| {
+            let rect = Rect::from_center_size(texture.loc, texture.dim);
+            (entity, rect, texture.loc_z)
+        })
+        .collect();
+    while let Some((entity, rect, z)) = stack.pop() {
+        let Ok((widget, flex, children)) = containers.get(entity) else {
+            continue;
+        };
+        let flex = flex.copied().unwrap_or_default();
+        let inset = widget.map_or(0, Widget::inset) + flex.padding;
+        let inner = Rect::from_corners(
+            rect.min + inset as f32,
+            (rect.max - inset as f32).max(rect.min + inset as f32),
+        );
+        let child_constraints: Vec<Constraint> = children
+            .iter()
+            .map(|child| constraints.get(*child).copied().unwrap_or_default())
+            .collect();
+        let along = match flex.direction {
+            FlexDirection::Column => inner.height(),
+            FlexDirection::Row => inner.width(),
+        };
+        let slots = split(along as u16, flex.gap, &child_constraints);
+        for (child, (offset, len)) in children.iter().zip(slots) {
+            let (offset, len) = (offset as f32, len as f32);
+            let child_rect = match flex.direction {
+                FlexDirection::Column => Rect::new(
+                    inner.min.x,
+                    inner.min.y + offset,
+                    inner.max.x,
+                    inner.min.y + offset + len,
+                ),
+                FlexDirection::Row => Rect::new(
+                    inner.min.x + offset,
+                    inner.min.y,
+                    inner.min.x + offset + len,
+                    inner.max.y,
+                ),
+            };
+            let Ok((mut texture, screen_space)) = nodes.get_mut(*child) else {
+                continue;
+            };
+            // Only touch what actually moved so the renderer doesn't redraw
+            // the whole UI every frame.
+            let child_z = z + 1.0;
+            if texture.loc != child_rect.center()
+                || texture.dim != child_rect.size()
+                || texture.loc_z != child_z
+            {
+                texture.loc = child_rect.center();
+                texture.dim = child_rect.size();
+                texture.loc_z = child_z;
+            }
+            let placed = ScreenSpace::new(
+                Anchor::TopLeft,
+                ScreenLength::Cells(child_rect.width() as i32),
+                ScreenLength::Cells(child_rect.height() as i32),
+            )
+            .with_offset(
+                ScreenLength::Cells(child_rect.min.x as i32),
+                ScreenLength::Cells(child_rect.min.y as i32),
+            );
+            match screen_space {
+                Some(mut screen_space) => {
+                    if *screen_space != placed {
+                        *screen_space = placed;
+                    }
+                }
+                None => {
+                    cmd.entity(*child).insert(placed);
+                }
+            }
+            stack.push((*child, child_rect, child_z));
+        }
+    }
+}
+
+/// Redraw the sprite of every widget which changed.
+fn draw_widgets(
+    mut cmd: Commands,
+    theme: Res<WidgetTheme>,
+    focus: Res<FocusStack>,
+    mut widgets: Query<(Entity, Ref<Widget>, &TextureRect, Option<&mut Sprite>)>,
+) {
+    let restyled = theme.is_changed() || focus.is_changed();
+    for (entity, widget, texture, sprite) in widgets.iter_mut() {
+        let size = texture.dim.ceil().as_uvec2();
+        let (width, height) = (size.x as u16, size.y as u16);
+        let focused = focus.is_focused(entity);
+        match sprite {
+            Some(sprite)
+                if !restyled
+                    && !widget.is_changed()
+                    && sprite.width() == width
+                    && sprite.height() == height => {}
+            Some(mut sprite) => *sprite = widget.draw(width, height, focused, &theme),
+            None => {
+                cmd.entity(entity)
+                    .insert(widget.draw(width, height, focused, &theme));
+            }
+        }
+    }
+}
+
+/// Write `text` from (`col`, `row`), stopping at `max_col`. Returns the column
+/// after the last glyph.
+fn put_str(
+    sprite: &mut Sprite,
+    mut col: u16,
+    row: u16,
+    text: &str,
+    style: CellStyle,
+    max_col: u16,
+) -> u16 {
+    for glyph in text.chars().filter(|glyph| !glyph.is_control()) {
+        let width = glyph_width(glyph);
+        if col + width > max_col {
+            break;
+        }
+        sprite.set(col, row, Some(Cell::new(glyph, style)));
+        col += width;
+    }
+    col
+}
+
+fn fill(sprite: &mut Sprite, row: u16, glyph: char, style: CellStyle) {
+    for col in 0..sprite.width() {
+        sprite.set(col, row, Some(Cell::new(glyph, style)));
+    }
+}
+
+/// Width of `text` in cells.
+fn text_width(text: &str) -> u16 {
+    text.chars().map(glyph_width).sum()
+}
+
+impl Widget {
+    /// Draw the widget into a sprite of the given size.
+    pub fn draw(&self, width: u16, height: u16, focused: bool, theme: &WidgetTheme) -> Sprite {
+        let mut sprite = Sprite::new(width, height);
+        if width == 0 || height == 0 {
+            return sprite;
+        }
+        // Widgets are opaque, nothing from the world shows through them.
+        for row in 0..height {
+            fill(&mut sprite, row, ' ', theme.text);
+        }
+        let selected = if focused {
+            theme.selected
+        } else {
+            theme.selected_unfocused
+        };
+        match self {
+            Widget::Panel(panel) => {
+                let style = if focused {
+                    theme.focused_border
+                } else {
+                    theme.border
+                };
+                draw_border(&mut sprite, style, panel.title.as_deref());
+            }
+            Widget::Label(text) => sprite = text.layout(width, height),
+            Widget::List(list) => {
+                let rows: Vec<Vec<(&str, u16, u16)>> = list
+                    .items
+                    .iter()
+                    .map(|item| vec![(item.as_str(), 0, width)])
+                    .collect();
+                draw_rows(
+                    &mut sprite,
+                    0,
+                    &rows,
+                    list.selected,
+                    list.scroll,
+                    selected,
+                    theme,
+                );
+            }
+            Widget::Table(table) => {
+                let constraints: Vec<Constraint> = table
+                    .columns
+                    .iter()
+                    .map(|(_, constraint)| *constraint)
+                    .collect();
+                // Leave room for the scrollbar.
+                let columns = split(width.saturating_sub(1), 1, &constraints);
+                for ((title, _), (start, len)) in table.columns.iter().zip(columns.iter()) {
+                    put_str(&mut sprite, *start, 0, title, theme.header, start + len);
+                }
+                let rows: Vec<Vec<(&str, u16, u16)>> = table
+                    .rows
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .zip(columns.iter())
+                            .map(|(cell, (start, len))| (cell.as_str(), *start, start + len))
+                            .collect()
+                    })
+                    .collect();
+                draw_rows(
+                    &mut sprite,
+                    1,
+                    &rows,
+                    table.selected,
+                    table.scroll,
+                    selected,
+                    theme,
+                );
+            }
+            Widget::ProgressBar(bar) => {
+                let filled = (bar.value.clamp(0.0, 1.0) * width as f32).round() as u16;
+                for row in 0..height {
+                    for col in 0..width {
+                        let cell = if col < filled {
+                            Cell::new('█', theme.bar_filled)
+                        } else {
+                            Cell::new('░', theme.bar_empty)
+                        };
+                        sprite.set(col, row, Some(cell));
+                    }
+                }
+                if let Some(label) = &bar.label {
+                    let start = width.saturating_sub(text_width(label)) / 2;
+                    put_str(&mut sprite, start, height / 2, label, theme.text, width);
+                }
+            }
+            Widget::Button(button) => {
+                let label = format!("[ {} ]", button.label);
+                let style = if focused { theme.selected } else { theme.text };
+                let start = width.saturating_sub(text_width(&label)) / 2;
+                put_str(&mut sprite, start, height / 2, &label, style, width);
+            }
+            Widget::Checkbox(checkbox) => {
+                let mark = if checkbox.checked { "[x] " } else { "[ ] " };
+                let style = if focused { theme.selected } else { theme.text };
+                let col = put_str(&mut sprite, 0, 0, mark, style, width);
+                put_str(&mut sprite, col, 0, &checkbox.label, theme.text, width);
+            }
+            Widget::TextInput(input) => {
+                let chars: Vec<char> = input.value.chars().collect();
+                let cursor = input.cursor.min(chars.len());
+                if chars.is_empty() && !focused {
+                    put_str(
+                        &mut sprite,
+                        0,
+                        0,
+                        &input.placeholder,
+                        theme.placeholder,
+                        width,
+                    );
+                }
+                // Scroll so the cursor stays in view.
+                let start = cursor.saturating_sub(width as usize - 1);
+                let visible: String = chars[start..].iter().collect();
+                put_str(&mut sprite, 0, 0, &visible, theme.text, width);
+                if focused {
+                    let col = text_width(&chars[start..cursor].iter().collect::<String>());
+                    let glyph = chars.get(cursor).copied().unwrap_or(' ');
+                    if col < width {
+                        sprite.set(col, 0, Some(Cell::new(glyph, theme.selected)));
+                    }
+                }
+            }
+        }
+        sprite
+    }
+}
+
+fn draw_border(sprite: &mut Sprite, style: CellStyle, title: Option<&str>) {
+    let (width, height) = (sprite.width(), sprite.height());
+    let (right, bottom) = (width - 1, height - 1);
+    for col in 0..width {
+        sprite.set(col, 0, Some(Cell::new('─', style)));
+        sprite.set(col, bottom, Some(Cell::new('─', style)));
+    }
+    for row in 0..height {
+        sprite.set(0, row, Some(Cell::new('│', style)));
+        sprite.set(right, row, Some(Cell::new('│', style)));
+    }
+    sprite.set(0, 0, Some(Cell::new('┌', style)));
+    sprite.set(right, 0, Some(Cell::new('┐', style)));
+    sprite.set(0, bottom, Some(Cell::new('└', style)));
+    sprite.set(right, bottom, Some(Cell::new('┘', style)));
+    if let Some(title) = title {
+        put_str(
+            sprite,
+            2,
+            0,
+            &format!(" {} ", title),
+            style,
+            right.saturating_sub(1),
+        );
+    }
+}
+
+/// Draw the rows of a list or table from `top` down, with a scrollbar on the
+/// right if they don't all fit. Each row is a set of (text, start, end)
+/// columns.
+fn draw_rows(
+    sprite: &mut Sprite,
+    top: u16,
+    rows: &[Vec<(&str, u16, u16)>],
+    selected: usize,
+    scroll: usize,
+    selected_style: CellStyle,
+    theme: &WidgetTheme,
+) {
+    let height = sprite.height().saturating_sub(top) as usize;
+    let width = sprite.width();
+    let scroll = scroll_to(
+        scroll.min(rows.len().saturating_sub(height)),
+        selected,
+        height,
+    );
+    let overflow = rows.len() > height;
+    let text_end = if overflow { width - 1 } else { width };
+    for (idx, row) in rows.iter().enumerate().skip(scroll).take(height) {
+        let y = top + (idx - scroll) as u16;
+        let style = if idx == selected {
+            selected_style
+        } else {
+            theme.text
+        };
+        if idx == selected {
+            for col in 0..text_end {
+                sprite.set(col, y, Some(Cell::new(' ', style)));
+            }
+        }
+        for (text, start, end) in row.iter() {
+            put_str(sprite, *start, y, text, style, (*end).min(text_end));
+        }
+    }
+    if overflow && height > 0 {
+        // Thumb sized and placed by how much of the rows are in view.
+        let thumb = (height * height / rows.len()).max(1);
+        let thumb_start = (scroll * height / rows.len()).min(height - thumb);
+        for idx in 0..height {
+            let glyph = if (thumb_start..thumb_start + thumb).contains(&idx) {
+                '█'
+            } else {
+                '│'
+            };
+            sprite.set(
+                width - 1,
+                top + idx as u16,
+                Some(Cell::new(glyph, theme.border)),
+            );
+        }
+    }
+}
+
+#[test]
+fn test_widgets() {
+    use super::backend::{HeadlessBackend, Terminal};
+    use crossterm::event::{Event, KeyCode as TermKeyCode, KeyEvent, KeyModifiers};
+
+    let backend = HeadlessBackend::new(14, 6);
+    let handle = backend.handle();
+    let mut app = App::new();
+    app.insert_resource(Terminal::new(backend))
+        .add_plugin(super::TerminalPlugin::default());
+    let root = app
+        .world
+        .spawn(
+            WidgetBundle::new(Widget::panel("Dwarves")).with_screen_space(ScreenSpace::new(
+                Anchor::TopLeft,
+                ScreenLength::Percent(100.0),
+                ScreenLength::Percent(100.0),
+            )),
+        )
+        .id();
+    let list = app
+        .world
+        .spawn(WidgetBundle::new(Widget::list([
+            "Urist", "Bomrek", "Kadol", "Zon", "Onul", "Lokum",
+        ])))
+        .id();
+    let bar = app
+        .world
+        .spawn(WidgetBundle::new(Widget::progress_bar(0.5)).with_constraint(Constraint::Length(1)))
+        .id();
+    app.world.entity_mut(root).push_children(&[list, bar]);
+    app.world.resource_mut::<FocusStack>().push(list);
+    app.update();
+    app.update();
+    assert_eq!(
+        handle.rows(),
+        vec![
+            "┌─ Dwarves ──┐",
+            "│Urist      █│",
+            "│Bomrek     ││",
+            "│Kadol      ││",
+            "│██████░░░░░░│",
+            "└────────────┘",
+        ]
+    );
+
+    app.world.send_event(TerminalKeyInput::Key(
+        bevy::input::keyboard::KeyboardInput {
+            scan_code: 0,
+            key_code: Some(KeyCode::End),
+            state: ButtonState::Pressed,
+        },
+    ));
+    app.update();
+    app.update();
+    let Widget::List(list) = app.world.get::<Widget>(list).unwrap() else {
+        panic!("not a list");
+    };
+    assert_eq!((list.selected, list.scroll), (5, 3));
+    assert_eq!(
+        handle.rows()[1..4],
+        ["│Zon        ││", "│Onul       █│", "│Lokum      ││"]
+    );
+
+    // Text comes from the typed characters rather than the keys.
+    let name = app
+        .world
+        .spawn(WidgetBundle::new(Widget::text_input("Name")))
+        .id();
+    app.world.resource_mut::<FocusStack>().push(name);
+    for char in "Ürist\n".chars() {
+        app.world.send_event(TerminalKeyInput::Char(char));
+    }
+    app.update();
+    let Widget::TextInput(input) = app.world.get::<Widget>(name).unwrap() else {
+        panic!("not a text input");
+    };
+    assert_eq!((input.value.as_str(), input.cursor), ("Ürist", 5));
+
+    // Edits typed within a single frame apply in the order they were typed.
+    for code in [
+        TermKeyCode::Char('a'),
+        TermKeyCode::Char('b'),
+        TermKeyCode::Backspace,
+        TermKeyCode::Char('c'),
+        TermKeyCode::Left,
+        TermKeyCode::Char('d'),
+    ] {
+        handle.push_event(Event::Key(KeyEvent::new(code, KeyModifiers::NONE)));
+    }
+    app.update();
+    let Widget::TextInput(input) = app.world.get::<Widget>(name).unwrap() else {
+        panic!("not a text input");
+    };
+    assert_eq!((input.value.as_str(), input.cursor), ("Üristadc", 7));
+}
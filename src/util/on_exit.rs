@@ -13,7 +13,7 @@ static SIGTERM_SIGNAL: Lazy<Arc<AtomicBool>> = Lazy::new(|| Arc::new(AtomicBool:
 // Note; Even though we use the static variable, we define this Resource to
 // prevent contention between users.
 #[derive(Resource)]
-struct OnExitCallbacks {}
+pub(crate) struct OnExitCallbacks {}
 
 pub struct OnExitPlugin {}
 
@@ -25,7 +25,7 @@ impl Plugin for OnExitPlugin {
             .add_event::<RegisterOnExit>()
             .add_system(check_sigterm_signal)
             .add_system(handle_register_onexit)
-            .add_system(handle_app_exit)
+            .add_system(handle_app_exit.in_base_set(CoreSet::Last))
             .add_system(handle_onexit);
 
         signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&SIGTERM_SIGNAL))
@@ -47,7 +47,10 @@ extern "C" fn on_exit() {
 }
 
 // We attempt to cleanly handle the app exiting and only rely on the libc::atexit behavior if we strictly need to.
-fn handle_app_exit(mut _callbacks: ResMut<OnExitCallbacks>, ev_recv: EventReader<AppExit>) {
+pub(crate) fn handle_app_exit(
+    mut _callbacks: ResMut<OnExitCallbacks>,
+    ev_recv: EventReader<AppExit>,
+) {
     if !ev_recv.is_empty() {
         on_exit();
     }